@@ -1,10 +1,11 @@
-use crate::token::{LiteralValue, Token};
+use crate::token::{LiteralValue, Span, Token};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
     Assign {
         name: Token,
         value: Box<Expr>,
+        depth: Option<usize>,
     },
     Binary {
         left: Box<Expr>,
@@ -21,10 +22,30 @@ pub enum Expr {
         name: Token,
     },
     Grouping {
-        expression: Box<Expr>
+        expression: Box<Expr>,
+        span: Span,
+    },
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    /// An anonymous function, `declaration` is always a `Stmt::Function`
+    Lambda {
+        declaration: Box<Stmt>,
+        span: Span,
+    },
+    List {
+        elements: Vec<Box<Expr>>,
+        span: Span,
     },
     Literal {
-        value: LiteralValue
+        value: LiteralValue,
+        span: Span,
+    },
+    Map {
+        entries: Vec<(Box<Expr>, Box<Expr>)>,
+        span: Span,
     },
     Logical {
         left: Box<Expr>,
@@ -36,12 +57,20 @@ pub enum Expr {
         name: Token,
         value: Box<Expr>,
     },
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
     Super {
         keyword: Token,
         method: Token,
+        depth: Option<usize>,
     },
     This {
         keyword: Token,
+        depth: Option<usize>,
     },
     Unary {
         operator: Token,
@@ -49,6 +78,7 @@ pub enum Expr {
     },
     Variable {
         name: Token,
+        depth: Option<usize>,
     },
 }
 
@@ -58,6 +88,7 @@ impl Expr {
         Expr::Assign {
             name,
             value: Box::new(value),
+            depth: None,
         }
     }
     pub fn new_binary(left: Expr, operator: Token, right: Expr) -> Expr {
@@ -80,14 +111,41 @@ impl Expr {
             name,
         }
     }
-    pub fn new_grouing(expression: Expr) -> Expr {
+    pub fn new_grouing(expression: Expr, span: Span) -> Expr {
         Expr::Grouping {
-            expression: Box::new(expression)
+            expression: Box::new(expression),
+            span,
+        }
+    }
+    pub fn new_index(object: Expr, bracket: Token, index: Expr) -> Expr {
+        Expr::Index {
+            object: Box::new(object),
+            bracket,
+            index: Box::new(index),
+        }
+    }
+    pub fn new_lambda(declaration: Stmt, span: Span) -> Expr {
+        Expr::Lambda {
+            declaration: Box::new(declaration),
+            span,
         }
     }
-    pub fn new_literal(value: LiteralValue) -> Expr {
+    pub fn new_list(elements: Vec<Expr>, span: Span) -> Expr {
+        Expr::List {
+            elements: elements.into_iter().map(Box::new).collect(),
+            span,
+        }
+    }
+    pub fn new_literal(value: LiteralValue, span: Span) -> Expr {
         Expr::Literal {
-            value
+            value,
+            span,
+        }
+    }
+    pub fn new_map(entries: Vec<(Expr, Expr)>, span: Span) -> Expr {
+        Expr::Map {
+            entries: entries.into_iter().map(|(key, value)| (Box::new(key), Box::new(value))).collect(),
+            span,
         }
     }
     pub fn new_logical(left: Expr, operator: Token, right: Expr) -> Expr {
@@ -104,15 +162,25 @@ impl Expr {
             value: Box::new(value),
         }
     }
+    pub fn new_set_index(object: Expr, bracket: Token, index: Expr, value: Expr) -> Expr {
+        Expr::SetIndex {
+            object: Box::new(object),
+            bracket,
+            index: Box::new(index),
+            value: Box::new(value),
+        }
+    }
     pub fn new_super(keyword: Token, method: Token) -> Expr {
         Expr::Super {
             keyword,
             method,
+            depth: None,
         }
     }
     pub fn new_this(keyword: Token) -> Expr {
         Expr::This {
-            keyword
+            keyword,
+            depth: None,
         }
     }
     pub fn new_unary(operator: Token, right: Expr) -> Expr {
@@ -124,7 +192,31 @@ impl Expr {
 
     pub fn new_variable(name: Token) -> Expr {
         Expr::Variable {
-            name
+            name,
+            depth: None,
+        }
+    }
+
+    /// Source covered by the whole expression
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign { name, value, .. } => { name.span.to(value.span()) }
+            Expr::Binary { left, right, .. } => { left.span().to(right.span()) }
+            Expr::Call { callee, paren, .. } => { callee.span().to(paren.span) }
+            Expr::Get { object, name } => { object.span().to(name.span) }
+            Expr::Grouping { span, .. } => { *span }
+            Expr::Index { object, bracket, .. } => { object.span().to(bracket.span) }
+            Expr::Lambda { span, .. } => { *span }
+            Expr::List { span, .. } => { *span }
+            Expr::Literal { span, .. } => { *span }
+            Expr::Map { span, .. } => { *span }
+            Expr::Logical { left, right, .. } => { left.span().to(right.span()) }
+            Expr::Set { object, value, .. } => { object.span().to(value.span()) }
+            Expr::SetIndex { object, value, .. } => { object.span().to(value.span()) }
+            Expr::Super { keyword, method, .. } => { keyword.span.to(method.span) }
+            Expr::This { keyword, .. } => { keyword.span }
+            Expr::Unary { operator, right } => { operator.span.to(right.span()) }
+            Expr::Variable { name, .. } => { name.span }
         }
     }
 }
@@ -134,11 +226,21 @@ pub enum Stmt {
     Block {
         statements: Vec<Box<Stmt>>
     },
+    Break {
+        keyword: Token,
+    },
     Class {
         name: Token,
-        superclass: Box<Expr>,
+        superclass: Option<Box<Expr>>,
         methods: Vec<Box<Stmt>>,
     },
+    Continue {
+        keyword: Token,
+    },
+    Export {
+        keyword: Token,
+        declaration: Box<Stmt>,
+    },
     Expression {
         expression: Box<Expr>,
     },
@@ -147,6 +249,12 @@ pub enum Stmt {
         params: Vec<Token>,
         body: Vec<Box<Stmt>>,
     },
+    /// `alias` is the name after `as`, without one the exports are defined directly
+    Import {
+        keyword: Token,
+        path: Token,
+        alias: Option<Token>,
+    },
     If {
         condition: Box<Expr>,
         then_branch: Box<Stmt>,
@@ -159,6 +267,18 @@ pub enum Stmt {
         keyword: Token,
         value: Option<Box<Expr>>,
     },
+    Throw {
+        keyword: Token,
+        value: Box<Expr>,
+    },
+    /// At least one of `catch` and `finally` is present, `catch` holds the
+    /// name the error is bound to and the handler's statements
+    Try {
+        keyword: Token,
+        body: Vec<Box<Stmt>>,
+        catch: Option<(Token, Vec<Box<Stmt>>)>,
+        finally: Option<Vec<Box<Stmt>>>,
+    },
     Variable {
         name: Token,
         initializer: Option<Box<Expr>>,
@@ -166,6 +286,9 @@ pub enum Stmt {
     While {
         condition: Box<Expr>,
         body: Box<Stmt>,
+        // Only set for desugared for loops, kept out of the body so that
+        // `continue` still runs it
+        increment: Option<Box<Expr>>,
     },
 }
 
@@ -175,13 +298,25 @@ impl Stmt {
             statements: statements.into_iter().map(|x| Box::new(x)).collect()
         }
     }
-    pub fn new_class(name: Token, superclass: Expr, methods: Vec<Stmt>) -> Stmt {
+    pub fn new_break(keyword: Token) -> Stmt {
+        Stmt::Break { keyword }
+    }
+    pub fn new_class(name: Token, superclass: Option<Expr>, methods: Vec<Stmt>) -> Stmt {
         Stmt::Class {
             name,
-            superclass: Box::new(superclass),
+            superclass: superclass.map(Box::new),
             methods: methods.into_iter().map(|x| Box::new(x)).collect(),
         }
     }
+    pub fn new_continue(keyword: Token) -> Stmt {
+        Stmt::Continue { keyword }
+    }
+    pub fn new_export(keyword: Token, declaration: Stmt) -> Stmt {
+        Stmt::Export {
+            keyword,
+            declaration: Box::new(declaration),
+        }
+    }
     pub fn new_expression(expression: Expr) -> Stmt {
         Stmt::Expression {
             expression: Box::new(expression)
@@ -212,6 +347,13 @@ impl Stmt {
             }
         }
     }
+    pub fn new_import(keyword: Token, path: Token, alias: Option<Token>) -> Stmt {
+        Stmt::Import {
+            keyword,
+            path,
+            alias,
+        }
+    }
     pub fn new_print(expression: Expr) -> Stmt {
         Stmt::Print {
             expression: Box::new(expression)
@@ -234,6 +376,22 @@ impl Stmt {
         }
         
     }
+    pub fn new_throw(keyword: Token, value: Expr) -> Stmt {
+        Stmt::Throw {
+            keyword,
+            value: Box::new(value),
+        }
+    }
+    pub fn new_try(keyword: Token, body: Vec<Stmt>, catch: Option<(Token, Vec<Stmt>)>,
+                   finally: Option<Vec<Stmt>>) -> Stmt {
+        let boxed = |statements: Vec<Stmt>| statements.into_iter().map(Box::new).collect::<Vec<Box<Stmt>>>();
+        Stmt::Try {
+            keyword,
+            body: boxed(body),
+            catch: catch.map(|(name, statements)| (name, boxed(statements))),
+            finally: finally.map(boxed),
+        }
+    }
     pub fn new_variable_initialized(name: Token, initializer: Expr) -> Stmt {
         Stmt::Variable {
             name,
@@ -247,15 +405,44 @@ impl Stmt {
             initializer: None,
         }
     }
-    pub fn new_while(condition: Expr, body: Stmt) -> Stmt {
+    pub fn new_while(condition: Expr, body: Stmt, increment: Option<Expr>) -> Stmt {
         Stmt::While {
             condition: Box::new(condition),
             body: Box::new(body),
+            increment: increment.map(Box::new),
+        }
+    }
+
+    /// Source to point at when the statement itself fails, blocks have none
+    /// since any error comes from one of their inner statements
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Stmt::Block { .. } => { None }
+            Stmt::Break { keyword } => { Some(keyword.span) }
+            Stmt::Class { name, .. } => { Some(name.span) }
+            Stmt::Continue { keyword } => { Some(keyword.span) }
+            Stmt::Export { declaration, .. } => { declaration.span() }
+            Stmt::Expression { expression } => { Some(expression.span()) }
+            Stmt::Function { name, .. } => { Some(name.span) }
+            Stmt::If { condition, .. } => { Some(condition.span()) }
+            Stmt::Import { keyword, path, .. } => { Some(keyword.span.to(path.span)) }
+            Stmt::Print { expression } => { Some(expression.span()) }
+            Stmt::Return { keyword, value } => {
+                match value {
+                    None => { Some(keyword.span) }
+                    Some(value) => { Some(keyword.span.to(value.span())) }
+                }
+            }
+            Stmt::Throw { keyword, value } => { Some(keyword.span.to(value.span())) }
+            Stmt::Try { keyword, .. } => { Some(keyword.span) }
+            Stmt::Variable { name, .. } => { Some(name.span) }
+            Stmt::While { condition, .. } => { Some(condition.span()) }
         }
     }
 }
 
-pub trait Visitor<T> {
+/// Walks the AST, expressions and statements may produce different results
+pub trait Visitor<T, S = T> {
     fn visit_expr(&mut self, expr: &mut Expr) -> T;
-    fn visit_stmt(&mut self, stmt: &mut Stmt) -> T;
+    fn visit_stmt(&mut self, stmt: &mut Stmt) -> S;
 }
\ No newline at end of file
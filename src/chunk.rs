@@ -0,0 +1,161 @@
+use crate::token::Span;
+use crate::vm::Value;
+
+/// Instructions understood by the bytecode virtual machine, operands follow
+/// the opcode byte in the chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant,     // u16 constant index
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,     // u8 stack slot
+    SetLocal,     // u8 stack slot
+    GetGlobal,    // u16 name constant
+    DefineGlobal, // u16 name constant
+    SetGlobal,    // u16 name constant
+    GetUpvalue,   // u8 upvalue index
+    SetUpvalue,   // u8 upvalue index
+    GetProperty,  // u16 name constant
+    SetProperty,  // u16 name constant
+    GetSuper,     // u16 name constant
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    SetResult,
+    Jump,         // u16 forward offset
+    JumpIfFalse,  // u16 forward offset
+    Loop,         // u16 backward offset
+    Call,         // u8 argument count
+    Closure,      // u16 function constant, then (is_local, index) per upvalue
+    CloseUpvalue,
+    Return,
+    Class,        // u16 name constant
+    Inherit,
+    Method,       // u16 name constant
+}
+
+const OPCODES: [OpCode; 39] = [
+    OpCode::Constant,
+    OpCode::Nil,
+    OpCode::True,
+    OpCode::False,
+    OpCode::Pop,
+    OpCode::GetLocal,
+    OpCode::SetLocal,
+    OpCode::GetGlobal,
+    OpCode::DefineGlobal,
+    OpCode::SetGlobal,
+    OpCode::GetUpvalue,
+    OpCode::SetUpvalue,
+    OpCode::GetProperty,
+    OpCode::SetProperty,
+    OpCode::GetSuper,
+    OpCode::Equal,
+    OpCode::NotEqual,
+    OpCode::Greater,
+    OpCode::GreaterEqual,
+    OpCode::Less,
+    OpCode::LessEqual,
+    OpCode::Add,
+    OpCode::Subtract,
+    OpCode::Multiply,
+    OpCode::Divide,
+    OpCode::Not,
+    OpCode::Negate,
+    OpCode::Print,
+    OpCode::SetResult,
+    OpCode::Jump,
+    OpCode::JumpIfFalse,
+    OpCode::Loop,
+    OpCode::Call,
+    OpCode::Closure,
+    OpCode::CloseUpvalue,
+    OpCode::Return,
+    OpCode::Class,
+    OpCode::Inherit,
+    OpCode::Method,
+];
+
+impl OpCode {
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OPCODES.get(byte as usize).copied()
+    }
+}
+
+/// A compiled sequence of instructions along with the constants it refers to.
+/// Source spans are run-length encoded as (first instruction offset, span) pairs.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk {
+            code: Vec::new(),
+            constants: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn write(&mut self, byte: u8, span: Span) {
+        self.code.push(byte);
+        match self.spans.last() {
+            Some((_, last_span)) if *last_span == span => {}
+            _ => { self.spans.push((self.code.len() - 1, span)) }
+        }
+    }
+
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
+        self.write(op as u8, span);
+    }
+
+    pub fn write_u16(&mut self, value: u16, span: Span) {
+        self.write((value >> 8) as u8, span);
+        self.write((value & 0xff) as u8, span);
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        ((self.code[offset] as u16) << 8) | self.code[offset + 1] as u16
+    }
+
+    /// Add a value to the constant pool, reusing an identical string or
+    /// number constant if there already is one
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        for (i, constant) in self.constants.iter().enumerate() {
+            let same = match (constant, &value) {
+                (Value::Number(a), Value::Number(b)) => { a.to_bits() == b.to_bits() }
+                (Value::Str(a), Value::Str(b)) => { a == b }
+                _ => { false }
+            };
+            if same {
+                return i;
+            }
+        }
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub fn span_at(&self, offset: usize) -> Span {
+        match self.spans.binary_search_by(|(start, _)| start.cmp(&offset)) {
+            Ok(index) => { self.spans[index].1 }
+            Err(0) => { Span::default() }
+            Err(index) => { self.spans[index - 1].1 }
+        }
+    }
+}
@@ -0,0 +1,768 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::ast::{Expr, Stmt};
+use crate::chunk::{Chunk, OpCode};
+use crate::token::{LiteralValue, Span, Token, TokenType};
+use crate::vm::{Function, Value};
+
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+/// Natives only the tree-walker has, as what they return needs lists
+const TREE_WALKER_NATIVES: [&str; 1] = ["args"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: String,
+    depth: Option<usize>, // None until the initializer has been compiled
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    index: u8,
+    is_local: bool,
+}
+
+/// Jumps out of a loop body waiting for the loop's end to be compiled
+struct LoopState {
+    scope_depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// Book-keeping for the function currently being compiled, one per nesting level
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    loops: Vec<LoopState>,
+}
+
+/// Compiles a resolved AST into bytecode for the virtual machine
+pub struct Compiler {
+    states: Vec<FunctionState>,
+    span: Span,
+    /// Globals the program or earlier runs define, which hide tree-walker natives
+    defined_globals: HashSet<String>,
+}
+
+pub struct CompileError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl FunctionState {
+    fn new(name: String, kind: FunctionKind) -> FunctionState {
+        // Slot zero holds the called closure, or the receiver for methods
+        let slot_zero = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => { "this".to_string() }
+            _ => { String::new() }
+        };
+        FunctionState {
+            function: Function {
+                name,
+                arity: 0,
+                upvalue_count: 0,
+                chunk: Chunk::new(),
+            },
+            kind,
+            locals: vec![Local { name: slot_zero, depth: Some(0), is_captured: false }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            states: Vec::new(),
+            span: Span::at_line(1),
+            defined_globals: HashSet::new(),
+        }
+    }
+
+    /// Tell the compiler which globals already exist, so code run after
+    /// them can use their names
+    pub fn define_globals<'a>(&mut self, names: impl Iterator<Item = &'a str>) {
+        self.defined_globals.extend(names.map(str::to_string));
+    }
+
+    /// Compile a whole program into the function run at the top level. The
+    /// value of the last top level statement is kept for the REPL to print.
+    pub fn compile(&mut self, statements: &Vec<Stmt>) -> Result<Function, CompileError> {
+        self.states.push(FunctionState::new("script".to_string(), FunctionKind::Script));
+        for statement in statements {
+            if let Stmt::Variable { name, .. } | Stmt::Function { name, .. } | Stmt::Class { name, .. } = statement {
+                self.defined_globals.insert(name.lexeme.clone());
+            }
+        }
+        for statement in statements {
+            if let Err(err) = self.top_level_statement(statement) {
+                self.states.clear();
+                return Err(err);
+            }
+        }
+        self.emit_op(OpCode::Nil);
+        self.emit_op(OpCode::Return);
+        let state = self.states.pop().expect("Compiler lost the script state");
+        Ok(state.function)
+    }
+
+    fn top_level_statement(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Expression { expression } => {
+                self.expression(expression)?;
+                self.emit_op(OpCode::SetResult);
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                // An if statement evaluates to whichever branch was taken
+                self.expression(condition)?;
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                self.top_level_statement(then_branch)?;
+                let else_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(then_jump)?;
+                self.emit_op(OpCode::Pop);
+                match else_branch {
+                    None => {
+                        self.emit_op(OpCode::Nil);
+                        self.emit_op(OpCode::SetResult);
+                    }
+                    Some(else_branch) => { self.top_level_statement(else_branch)?; }
+                }
+                self.patch_jump(else_jump)?;
+            }
+            _ => {
+                self.statement(stmt)?;
+                self.emit_op(OpCode::Nil);
+                self.emit_op(OpCode::SetResult);
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                for statement in statements {
+                    self.statement(statement)?;
+                }
+                self.end_scope();
+            }
+            Stmt::Break { keyword } => {
+                let jump = self.loop_jump(keyword)?;
+                if let Some(loop_state) = self.current_mut().loops.last_mut() {
+                    loop_state.breaks.push(jump);
+                }
+            }
+            Stmt::Class { name, superclass, methods } => {
+                self.span = name.span;
+                let name_constant = self.identifier_constant(name)?;
+                self.declare_variable(name)?;
+                self.emit_op(OpCode::Class);
+                self.emit_u16(name_constant);
+                self.define_variable(name_constant);
+
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable { name: superclass_name, .. } = superclass.as_ref() {
+                        if superclass_name.lexeme == name.lexeme {
+                            return Err(CompileError {
+                                token: superclass_name.clone(),
+                                message: "A class can't inherit from itself.".to_string(),
+                            });
+                        }
+                    }
+                    self.expression(superclass)?;
+
+                    self.begin_scope();
+                    self.add_local("super".to_string())?;
+                    self.mark_initialized();
+
+                    self.named_variable(name, false)?;
+                    self.span = superclass.span();
+                    self.emit_op(OpCode::Inherit);
+                }
+
+                self.named_variable(name, false)?;
+                for method in methods {
+                    if let Stmt::Function { name: method_name, params, body } = method.as_ref() {
+                        let kind = if method_name.lexeme == "init" {
+                            FunctionKind::Initializer
+                        } else {
+                            FunctionKind::Method
+                        };
+                        self.function(method_name, params, body, kind)?;
+                        let method_constant = self.identifier_constant(method_name)?;
+                        self.emit_op(OpCode::Method);
+                        self.emit_u16(method_constant);
+                    }
+                }
+                self.emit_op(OpCode::Pop);
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+            Stmt::Continue { keyword } => {
+                let jump = self.loop_jump(keyword)?;
+                if let Some(loop_state) = self.current_mut().loops.last_mut() {
+                    loop_state.continues.push(jump);
+                }
+            }
+            Stmt::Export { declaration, .. } => {
+                self.statement(declaration)?;
+            }
+            Stmt::Expression { expression } => {
+                self.expression(expression)?;
+                self.emit_op(OpCode::Pop);
+            }
+            Stmt::Import { keyword, .. } => {
+                return Err(CompileError {
+                    token: keyword.clone(),
+                    message: "Modules are only supported by the tree-walking interpreter.".to_string(),
+                });
+            }
+            Stmt::Function { name, params, body } => {
+                self.span = name.span;
+                let name_constant = self.identifier_constant(name)?;
+                self.declare_variable(name)?;
+                // Functions may refer to themselves, so the name is usable right away
+                self.mark_initialized();
+                self.function(name, params, body, FunctionKind::Function)?;
+                self.define_variable(name_constant);
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.expression(condition)?;
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                self.statement(then_branch)?;
+                let else_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(then_jump)?;
+                self.emit_op(OpCode::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(else_jump)?;
+            }
+            Stmt::Print { expression } => {
+                self.expression(expression)?;
+                self.emit_op(OpCode::Print);
+            }
+            Stmt::Return { keyword, value } => {
+                self.span = keyword.span;
+                if self.current().kind == FunctionKind::Script {
+                    return Err(CompileError {
+                        token: keyword.clone(),
+                        message: "Can't return from top-level code.".to_string(),
+                    });
+                }
+                match value {
+                    None => { self.emit_return(); }
+                    Some(value) => {
+                        if self.current().kind == FunctionKind::Initializer {
+                            return Err(CompileError {
+                                token: keyword.clone(),
+                                message: "Can't return a value from an initializer.".to_string(),
+                            });
+                        }
+                        self.expression(value)?;
+                        self.emit_op(OpCode::Return);
+                    }
+                }
+            }
+            Stmt::Throw { keyword, .. } | Stmt::Try { keyword, .. } => {
+                return Err(CompileError {
+                    token: keyword.clone(),
+                    message: "Exceptions are only supported by the tree-walking interpreter.".to_string(),
+                });
+            }
+            Stmt::Variable { name, initializer } => {
+                self.span = name.span;
+                let name_constant = self.identifier_constant(name)?;
+                self.declare_variable(name)?;
+                match initializer {
+                    None => { self.emit_op(OpCode::Nil); }
+                    Some(initializer) => { self.expression(initializer)?; }
+                }
+                self.define_variable(name_constant);
+            }
+            Stmt::While { condition, body, increment } => {
+                let loop_start = self.current_chunk().code.len();
+                self.expression(condition)?;
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+
+                let scope_depth = self.current().scope_depth;
+                self.current_mut().loops.push(LoopState { scope_depth, breaks: Vec::new(), continues: Vec::new() });
+                let body_result = self.statement(body);
+                let loop_state = self.current_mut().loops.pop().expect("Compiler lost the loop state");
+                body_result?;
+
+                for jump in loop_state.continues {
+                    self.patch_jump(jump)?;
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment)?;
+                    self.emit_op(OpCode::Pop);
+                }
+                self.emit_loop(loop_start)?;
+                self.patch_jump(exit_jump)?;
+                self.emit_op(OpCode::Pop);
+                // Breaks land after the pop since they left the condition's value behind already
+                for jump in loop_state.breaks {
+                    self.patch_jump(jump)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match expr {
+            Expr::Assign { name, value, .. } => {
+                self.expression(value)?;
+                self.named_variable(name, true)?;
+            }
+            Expr::Binary { left, operator, right } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.span = expr.span();
+                let op = match operator.token_type {
+                    TokenType::Minus => { OpCode::Subtract }
+                    TokenType::Plus => { OpCode::Add }
+                    TokenType::Slash => { OpCode::Divide }
+                    TokenType::Star => { OpCode::Multiply }
+                    TokenType::BangEqual => { OpCode::NotEqual }
+                    TokenType::EqualEqual => { OpCode::Equal }
+                    TokenType::Greater => { OpCode::Greater }
+                    TokenType::GreaterEqual => { OpCode::GreaterEqual }
+                    TokenType::Less => { OpCode::Less }
+                    TokenType::LessEqual => { OpCode::LessEqual }
+                    _ => {
+                        return Err(CompileError {
+                            token: operator.clone(),
+                            message: "Invalid Binary Operator".to_string(),
+                        });
+                    }
+                };
+                self.emit_op(op);
+            }
+            Expr::Call { callee, arguments, .. } => {
+                self.expression(callee)?;
+                for argument in arguments {
+                    self.expression(argument)?;
+                }
+                self.span = expr.span();
+                self.emit_op(OpCode::Call);
+                self.emit_byte(arguments.len() as u8);
+            }
+            Expr::Get { object, name } => {
+                self.expression(object)?;
+                self.span = expr.span();
+                let name_constant = self.identifier_constant(name)?;
+                self.emit_op(OpCode::GetProperty);
+                self.emit_u16(name_constant);
+            }
+            Expr::Grouping { expression, .. } => {
+                self.expression(expression)?;
+            }
+            Expr::Index { bracket, .. } | Expr::SetIndex { bracket, .. } => {
+                return Err(CompileError {
+                    token: bracket.clone(),
+                    message: "Indexing is only supported by the tree-walking interpreter.".to_string(),
+                });
+            }
+            Expr::Lambda { declaration, .. } => {
+                if let Stmt::Function { name, params, body } = declaration.as_ref() {
+                    self.span = expr.span();
+                    self.function(name, params, body, FunctionKind::Function)?;
+                }
+            }
+            Expr::List { span, .. } => {
+                return Err(CompileError {
+                    token: Token::spanning(TokenType::LeftBracket, "[".to_string(), LiteralValue::None, *span),
+                    message: "Lists are only supported by the tree-walking interpreter.".to_string(),
+                });
+            }
+            Expr::Map { span, .. } => {
+                return Err(CompileError {
+                    token: Token::spanning(TokenType::LeftBrace, "{".to_string(), LiteralValue::None, *span),
+                    message: "Maps are only supported by the tree-walking interpreter.".to_string(),
+                });
+            }
+            Expr::Literal { value, .. } => {
+                self.span = expr.span();
+                match value {
+                    LiteralValue::None => { self.emit_op(OpCode::Nil); }
+                    LiteralValue::True => { self.emit_op(OpCode::True); }
+                    LiteralValue::False => { self.emit_op(OpCode::False); }
+                    LiteralValue::NumValue(num) => { self.emit_constant(Value::Number(*num))?; }
+                    LiteralValue::StringValue(s) => { self.emit_constant(Value::Str(Rc::new(s.clone())))?; }
+                    _ => {
+                        return Err(CompileError {
+                            token: self.synthetic_token(),
+                            message: "Invalid literal value".to_string(),
+                        });
+                    }
+                }
+            }
+            Expr::Logical { left, operator, right } => {
+                self.expression(left)?;
+                self.span = operator.span;
+                if operator.token_type == TokenType::Or {
+                    let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                    let end_jump = self.emit_jump(OpCode::Jump);
+                    self.patch_jump(else_jump)?;
+                    self.emit_op(OpCode::Pop);
+                    self.expression(right)?;
+                    self.patch_jump(end_jump)?;
+                } else {
+                    let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+                    self.emit_op(OpCode::Pop);
+                    self.expression(right)?;
+                    self.patch_jump(end_jump)?;
+                }
+            }
+            Expr::Set { object, name, value } => {
+                self.expression(object)?;
+                self.expression(value)?;
+                self.span = expr.span();
+                let name_constant = self.identifier_constant(name)?;
+                self.emit_op(OpCode::SetProperty);
+                self.emit_u16(name_constant);
+            }
+            Expr::Super { keyword, method, .. } => {
+                self.span = expr.span();
+                let this_token = Token::spanning(TokenType::This, "this".to_string(), LiteralValue::None, keyword.span);
+                self.named_variable(&this_token, false)?;
+                self.named_variable(keyword, false)?;
+                let name_constant = self.identifier_constant(method)?;
+                self.emit_op(OpCode::GetSuper);
+                self.emit_u16(name_constant);
+            }
+            Expr::This { keyword, .. } => {
+                self.named_variable(keyword, false)?;
+            }
+            Expr::Unary { operator, right } => {
+                self.expression(right)?;
+                self.span = expr.span();
+                match operator.token_type {
+                    TokenType::Minus => { self.emit_op(OpCode::Negate); }
+                    TokenType::Bang => { self.emit_op(OpCode::Not); }
+                    _ => {
+                        return Err(CompileError {
+                            token: operator.clone(),
+                            message: "Invalid Unary Operator".to_string(),
+                        });
+                    }
+                }
+            }
+            Expr::Variable { name, .. } => {
+                self.named_variable(name, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, name: &Token, params: &Vec<Token>, body: &Vec<Box<Stmt>>,
+                kind: FunctionKind) -> Result<(), CompileError> {
+        self.states.push(FunctionState::new(name.lexeme.clone(), kind));
+        self.begin_scope();
+
+        for param in params {
+            self.current_mut().function.arity += 1;
+            self.declare_variable(param)?;
+            self.mark_initialized();
+        }
+        for statement in body {
+            self.statement(statement)?;
+        }
+        self.emit_return();
+
+        let state = self.states.pop().expect("Compiler lost a function state");
+        let mut function = state.function;
+        function.upvalue_count = state.upvalues.len();
+
+        let function_constant = self.make_constant(Value::Function(Rc::new(function)))?;
+        self.emit_op(OpCode::Closure);
+        self.emit_u16(function_constant);
+        for upvalue in state.upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+        Ok(())
+    }
+
+    fn named_variable(&mut self, name: &Token, assign: bool) -> Result<(), CompileError> {
+        self.span = name.span;
+        let level = self.states.len() - 1;
+        let (op, operand) = if let Some(slot) = self.resolve_local(level, name)? {
+            (if assign { OpCode::SetLocal } else { OpCode::GetLocal }, slot as u16)
+        } else if let Some(index) = self.resolve_upvalue(level, name)? {
+            (if assign { OpCode::SetUpvalue } else { OpCode::GetUpvalue }, index as u16)
+        } else {
+            if TREE_WALKER_NATIVES.contains(&name.lexeme.as_str()) && !self.defined_globals.contains(&name.lexeme) {
+                return Err(CompileError {
+                    token: name.clone(),
+                    message: format!("{}() is only available in the tree-walking interpreter.", name.lexeme),
+                });
+            }
+            let name_constant = self.identifier_constant(name)?;
+            self.emit_op(if assign { OpCode::SetGlobal } else { OpCode::GetGlobal });
+            self.emit_u16(name_constant);
+            return Ok(());
+        };
+        self.emit_op(op);
+        self.emit_byte(operand as u8);
+        Ok(())
+    }
+
+    fn resolve_local(&self, level: usize, name: &Token) -> Result<Option<usize>, CompileError> {
+        for (slot, local) in self.states[level].locals.iter().enumerate().rev() {
+            if local.name == name.lexeme {
+                if local.depth.is_none() {
+                    return Err(CompileError {
+                        token: name.clone(),
+                        message: "Can't read local variable in its own initializer.".to_string(),
+                    });
+                }
+                return Ok(Some(slot));
+            }
+        }
+        Ok(None)
+    }
+
+    fn resolve_upvalue(&mut self, level: usize, name: &Token) -> Result<Option<u8>, CompileError> {
+        if level == 0 {
+            return Ok(None);
+        }
+        if let Some(slot) = self.resolve_local(level - 1, name)? {
+            self.states[level - 1].locals[slot].is_captured = true;
+            return Ok(Some(self.add_upvalue(level, name, slot as u8, true)?));
+        }
+        if let Some(index) = self.resolve_upvalue(level - 1, name)? {
+            return Ok(Some(self.add_upvalue(level, name, index, false)?));
+        }
+        Ok(None)
+    }
+
+    fn add_upvalue(&mut self, level: usize, name: &Token, index: u8, is_local: bool) -> Result<u8, CompileError> {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &mut self.states[level].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return Ok(existing as u8);
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            return Err(CompileError {
+                token: name.clone(),
+                message: "Too many closure variables in function.".to_string(),
+            });
+        }
+        upvalues.push(upvalue);
+        Ok((upvalues.len() - 1) as u8)
+    }
+
+    fn declare_variable(&mut self, name: &Token) -> Result<(), CompileError> {
+        if self.current().scope_depth == 0 {
+            return Ok(());
+        }
+        if self.current().locals.len() == MAX_LOCALS {
+            return Err(CompileError {
+                token: name.clone(),
+                message: "Too many local variables in function.".to_string(),
+            });
+        }
+        self.current_mut().locals.push(Local {
+            name: name.lexeme.clone(),
+            depth: None,
+            is_captured: false,
+        });
+        Ok(())
+    }
+
+    fn add_local(&mut self, name: String) -> Result<(), CompileError> {
+        let token = Token::spanning(TokenType::Identifier, name, LiteralValue::None, self.span);
+        self.declare_variable(&token)
+    }
+
+    fn mark_initialized(&mut self) {
+        let state = self.current_mut();
+        if state.scope_depth == 0 {
+            return;
+        }
+        let depth = state.scope_depth;
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(depth);
+        }
+    }
+
+    fn define_variable(&mut self, name_constant: u16) {
+        // Locals simply stay on the stack where the initializer left them
+        if self.current().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+        self.emit_op(OpCode::DefineGlobal);
+        self.emit_u16(name_constant);
+    }
+
+    fn begin_scope(&mut self) {
+        self.current_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.current_mut().scope_depth -= 1;
+        loop {
+            let state = self.current();
+            let captured = match state.locals.last() {
+                Some(Local { depth: Some(depth), is_captured, .. }) if *depth > state.scope_depth => { *is_captured }
+                _ => { break; }
+            };
+            if captured {
+                self.emit_op(OpCode::CloseUpvalue);
+            } else {
+                self.emit_op(OpCode::Pop);
+            }
+            self.current_mut().locals.pop();
+        }
+    }
+
+    /// Drop the locals declared inside the innermost loop, without forgetting
+    /// them since the rest of the block still refers to their slots, and emit
+    /// the jump for a `break` or `continue`
+    fn loop_jump(&mut self, keyword: &Token) -> Result<usize, CompileError> {
+        self.span = keyword.span;
+        let loop_depth = match self.current().loops.last() {
+            Some(loop_state) => { loop_state.scope_depth }
+            None => {
+                return Err(CompileError {
+                    token: keyword.clone(),
+                    message: format!("Can't use '{}' outside of a loop.", keyword.lexeme),
+                });
+            }
+        };
+        let captured: Vec<bool> = self.current().locals.iter().rev()
+            .take_while(|local| matches!(local.depth, Some(depth) if depth > loop_depth))
+            .map(|local| local.is_captured)
+            .collect();
+        for is_captured in captured {
+            if is_captured {
+                self.emit_op(OpCode::CloseUpvalue);
+            } else {
+                self.emit_op(OpCode::Pop);
+            }
+        }
+        Ok(self.emit_jump(OpCode::Jump))
+    }
+
+    fn identifier_constant(&mut self, name: &Token) -> Result<u16, CompileError> {
+        self.make_constant(Value::Str(Rc::new(name.lexeme.clone())))
+    }
+
+    fn make_constant(&mut self, value: Value) -> Result<u16, CompileError> {
+        let index = self.current_chunk().add_constant(value);
+        if index > u16::MAX as usize {
+            return Err(CompileError {
+                token: self.synthetic_token(),
+                message: "Too many constants in one chunk.".to_string(),
+            });
+        }
+        Ok(index as u16)
+    }
+
+    fn emit_constant(&mut self, value: Value) -> Result<(), CompileError> {
+        let index = self.make_constant(value)?;
+        self.emit_op(OpCode::Constant);
+        self.emit_u16(index);
+        Ok(())
+    }
+
+    fn emit_return(&mut self) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_byte(0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        self.emit_u16(0xffff);
+        self.current_chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) -> Result<(), CompileError> {
+        let jump = self.current_chunk().code.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            return Err(CompileError {
+                token: self.synthetic_token(),
+                message: "Too much code to jump over.".to_string(),
+            });
+        }
+        let chunk = self.current_chunk();
+        chunk.code[offset] = (jump >> 8) as u8;
+        chunk.code[offset + 1] = (jump & 0xff) as u8;
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) -> Result<(), CompileError> {
+        self.emit_op(OpCode::Loop);
+        let offset = self.current_chunk().code.len() - loop_start + 2;
+        if offset > u16::MAX as usize {
+            return Err(CompileError {
+                token: self.synthetic_token(),
+                message: "Loop body too large.".to_string(),
+            });
+        }
+        self.emit_u16(offset as u16);
+        Ok(())
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        let span = self.span;
+        self.current_chunk().write_op(op, span);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let span = self.span;
+        self.current_chunk().write(byte, span);
+    }
+
+    fn emit_u16(&mut self, value: u16) {
+        let span = self.span;
+        self.current_chunk().write_u16(value, span);
+    }
+
+    fn current(&self) -> &FunctionState {
+        self.states.last().expect("Compiler has no active function")
+    }
+
+    fn current_mut(&mut self) -> &mut FunctionState {
+        self.states.last_mut().expect("Compiler has no active function")
+    }
+
+    fn current_chunk(&mut self) -> &mut Chunk {
+        &mut self.current_mut().function.chunk
+    }
+
+    /// Token used to report errors that aren't tied to a single source token
+    fn synthetic_token(&self) -> Token {
+        Token::spanning(TokenType::Eof, String::new(), LiteralValue::None, self.span)
+    }
+}
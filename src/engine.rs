@@ -0,0 +1,464 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+use crate::gc::GcRef;
+use crate::interpreter::{self, Interpreter, InterpreterError, StackFrame};
+use crate::lox_budget::{InterruptHandle, LimitExceeded, Limits};
+use crate::lox_host::HostRef;
+use crate::lox_map::{LoxMap, MapKey};
+use crate::lox_native::{Args, Arity};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Lexer;
+use crate::token::{LiteralValue, Span};
+
+/// Runs Lox code for a Rust host. Globals, functions and classes defined by
+/// one `eval` are visible to the next, like lines typed into the REPL.
+pub struct Engine {
+    interpreter: Interpreter,
+}
+
+/// A value passed between the host and Lox. Lists and maps are copied in and
+/// out, functions, classes and instances stay in the engine and are passed
+/// around as handles.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Function(Handle),
+    Class(Handle),
+    Instance(Handle),
+    /// A Rust object scripts can use through its `HostObject` methods
+    Host(HostRef),
+}
+
+/// A function, class or instance owned by an engine. The engine keeps it
+/// alive until the handle and all its clones are dropped, and it only means
+/// something to the engine it came from.
+#[derive(Clone)]
+pub struct Handle(Rc<RootSlot>);
+
+/// The values handles refer to, each under the id of the slot rooting it
+#[derive(Default)]
+pub(crate) struct HostRoots {
+    next_id: usize,
+    values: HashMap<usize, LiteralValue>,
+}
+
+/// One entry in `HostRoots`, removed again when the last handle sharing it is dropped
+struct RootSlot {
+    id: usize,
+    value: LiteralValue,
+    roots: Weak<RefCell<HostRoots>>,
+}
+
+/// Why running code for the host failed
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// The source didn't scan, parse or resolve, every problem found is listed
+    Compile(Vec<Diagnostic>),
+    /// A runtime error or an uncaught `throw`
+    Runtime {
+        message: String,
+        line: Option<i32>,
+        stack: Vec<StackFrame>,
+    },
+    /// The script called `exit()` with this status
+    Exit(u8),
+    /// The script went over one of the engine's `Limits` or was interrupted
+    Limit {
+        kind: LimitExceeded,
+        message: String,
+    },
+}
+
+/// One problem found before the code ran
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub line: i32,
+    pub column: usize,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine { interpreter: Interpreter::new() }
+    }
+
+    /// Disable the file natives and `import`, see `Interpreter::set_sandboxed`
+    pub fn set_sandboxed(&mut self, sandboxed: bool) {
+        self.interpreter.set_sandboxed(sandboxed);
+    }
+
+    /// Limit the steps, call depth and time each `eval` or call may use
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.interpreter.set_limits(limits);
+    }
+
+    /// How big the stack of the thread calling into the engine is, 2 MiB
+    /// unless set. Scripts recursing deeper than it can hold fail with
+    /// `LimitExceeded::CallDepth` instead of overflowing it.
+    pub fn set_stack_size(&mut self, bytes: usize) {
+        self.interpreter.set_stack_size(bytes);
+    }
+
+    /// A handle another thread can use to stop whatever the engine is running
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interpreter.interrupt_handle()
+    }
+
+    /// Make a Rust closure callable from Lox as a global function called `name`
+    pub fn register_native(&mut self, name: &str, arity: Arity, function: impl Fn(&Args) -> Result<Value, Error> + 'static) {
+        self.interpreter.register_native(name, arity, function);
+    }
+
+    /// Run a piece of source, returning the value of its last statement
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let mut lexer = Lexer::new(source.to_string());
+        lexer.scan_tokens();
+        let mut diagnostics: Vec<Diagnostic> = lexer.errors.iter()
+            .map(|error| Diagnostic::new(&error.message, error.span))
+            .collect();
+
+        let mut statements = match Parser::new(Vec::from(lexer.tokens)).parse() {
+            Ok(statements) => { statements }
+            Err(errors) => {
+                diagnostics.extend(errors.iter().map(|error| Diagnostic::new(&error.message, error.token.span)));
+                return Err(Error::Compile(diagnostics));
+            }
+        };
+        if !diagnostics.is_empty() {
+            return Err(Error::Compile(diagnostics));
+        }
+
+        if let Err(errors) = Resolver::new().resolve(&mut statements) {
+            let diagnostics = errors.iter().map(|error| Diagnostic::new(&error.message, error.token.span)).collect();
+            return Err(Error::Compile(diagnostics));
+        }
+
+        let result = self.interpreter.interpret(&mut statements).map_err(Error::from)?;
+        to_host(&mut self.interpreter, &result)
+    }
+
+    /// Read a global variable, functions and classes declared at the top level included
+    pub fn get_global(&mut self, name: &str) -> Result<Value, Error> {
+        let globals = self.interpreter.globals;
+        let value = match self.interpreter.heap.env(globals).values.get(name) {
+            Some(value) => { value.clone() }
+            None => { return Err(Error::runtime(format!("Undefined variable '{name}'."))); }
+        };
+        to_host(&mut self.interpreter, &value)
+    }
+
+    /// Define a global variable, replacing any existing one with the same name
+    pub fn set_global(&mut self, name: &str, value: Value) -> Result<(), Error> {
+        let value = to_lox(&mut self.interpreter, &value)?;
+        let globals = self.interpreter.globals;
+        self.interpreter.heap.env_mut(globals).define(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Call the global function or class called `name`
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, Error> {
+        let callee = self.get_global(name)?;
+        self.call_value(&callee, args)
+    }
+
+    /// Call a function or class handle the engine handed out earlier
+    pub fn call_value(&mut self, callee: &Value, args: &[Value]) -> Result<Value, Error> {
+        let callee = match callee {
+            Value::Function(callee) | Value::Class(callee) => { checked_handle(&self.interpreter, callee)?.clone() }
+            _ => { return Err(Error::runtime("Tried to call non-callable".to_string())); }
+        };
+        let mut arguments = Vec::with_capacity(args.len());
+        for arg in args {
+            arguments.push(to_lox(&mut self.interpreter, arg)?);
+        }
+        self.interpreter.start_budget();
+        // Calls from the host have no line in the script to point at
+        let result = self.interpreter.call_value(callee, arguments, 0).map_err(Error::from)?;
+        to_host(&mut self.interpreter, &result)
+    }
+}
+
+/// Copy a Lox value out for the host, rooting anything passed as a handle
+pub(crate) fn to_host(interpreter: &mut Interpreter, value: &LiteralValue) -> Result<Value, Error> {
+    to_host_nested(interpreter, value, &mut Vec::new())
+}
+
+fn to_host_nested(interpreter: &mut Interpreter, value: &LiteralValue, open: &mut Vec<GcRef>) -> Result<Value, Error> {
+    match value {
+        LiteralValue::None => { Ok(Value::Nil) }
+        LiteralValue::True => { Ok(Value::Bool(true)) }
+        LiteralValue::False => { Ok(Value::Bool(false)) }
+        LiteralValue::NumValue(num) => { Ok(Value::Number(*num)) }
+        LiteralValue::StringValue(s) | LiteralValue::IdentifierValue(s) => { Ok(Value::String(s.clone())) }
+        LiteralValue::List(list) => {
+            // Copies can't share structure, so a list inside itself has no host form
+            if open.contains(list) {
+                return Err(Error::runtime("Can't pass a list that contains itself to the host.".to_string()));
+            }
+            open.push(*list);
+            let elements = interpreter.heap.list(*list).clone();
+            let mut converted = Vec::with_capacity(elements.len());
+            for element in &elements {
+                converted.push(to_host_nested(interpreter, element, open)?);
+            }
+            open.pop();
+            Ok(Value::List(converted))
+        }
+        LiteralValue::Map(map) => {
+            if open.contains(map) {
+                return Err(Error::runtime("Can't pass a map that contains itself to the host.".to_string()));
+            }
+            open.push(*map);
+            let entries = interpreter.heap.map(*map).entries().clone();
+            let mut converted = Vec::with_capacity(entries.len());
+            for (key, element) in &entries {
+                converted.push((to_host_nested(interpreter, &key.to_value(), open)?, to_host_nested(interpreter, element, open)?));
+            }
+            open.pop();
+            Ok(Value::Map(converted))
+        }
+        LiteralValue::Function(_) => {
+            Ok(Value::Function(interpreter.root_for_host(value)))
+        }
+        LiteralValue::Class(_) => {
+            Ok(Value::Class(interpreter.root_for_host(value)))
+        }
+        LiteralValue::Instance(_) => {
+            Ok(Value::Instance(interpreter.root_for_host(value)))
+        }
+        LiteralValue::Host(object) => { Ok(Value::Host(object.clone())) }
+    }
+}
+
+/// Build the Lox form of a host value, allocating any lists and maps
+pub(crate) fn to_lox(interpreter: &mut Interpreter, value: &Value) -> Result<LiteralValue, Error> {
+    match value {
+        Value::Nil => { Ok(LiteralValue::None) }
+        Value::Bool(true) => { Ok(LiteralValue::True) }
+        Value::Bool(false) => { Ok(LiteralValue::False) }
+        Value::Number(num) => { Ok(LiteralValue::NumValue(*num)) }
+        Value::String(s) => { Ok(LiteralValue::StringValue(s.clone())) }
+        Value::List(elements) => {
+            let mut converted = Vec::with_capacity(elements.len());
+            for element in elements {
+                converted.push(to_lox(interpreter, element)?);
+            }
+            Ok(LiteralValue::List(interpreter.heap.alloc_list(converted)))
+        }
+        Value::Map(entries) => {
+            let mut map = LoxMap::new();
+            for (key, element) in entries {
+                let key = MapKey::from_value(&to_lox(interpreter, key)?).map_err(Error::from)?;
+                map.insert(key, to_lox(interpreter, element)?);
+            }
+            Ok(LiteralValue::Map(interpreter.heap.alloc_map(map)))
+        }
+        Value::Function(handle) | Value::Class(handle) | Value::Instance(handle) => {
+            Ok(checked_handle(interpreter, handle)?.clone())
+        }
+        Value::Host(object) => { Ok(LiteralValue::Host(object.clone())) }
+    }
+}
+
+/// What a handle refers to, as long as it came from this interpreter. Another
+/// engine's objects live in a different heap, so their references mean nothing here.
+fn checked_handle<'a>(interpreter: &Interpreter, handle: &'a Handle) -> Result<&'a LiteralValue, Error> {
+    if !interpreter.owns_handle(handle) {
+        return Err(Error::runtime("Handle belongs to a different engine.".to_string()));
+    }
+    Ok(handle.value())
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+/// Values print the way Lox's `print` shows them
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(num) => write!(f, "{num}"),
+            Value::String(s) => write!(f, "{s}"),
+            Value::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_element(f)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, element)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    key.fmt_element(f)?;
+                    write!(f, ": ")?;
+                    element.fmt_element(f)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Function(handle) | Value::Class(handle) | Value::Instance(handle) => {
+                write!(f, "{}", handle.value())
+            }
+            Value::Host(object) => write!(f, "{object}"),
+        }
+    }
+}
+
+impl Value {
+    /// Strings inside a list or map are quoted, as they are by `print`
+    fn fmt_element(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "\"{s}\""),
+            _ => write!(f, "{self}"),
+        }
+    }
+}
+
+impl Handle {
+    fn value(&self) -> &LiteralValue {
+        &self.0.value
+    }
+}
+
+/// Handles are equal when they refer to the same function, class or instance
+impl PartialEq for Handle {
+    fn eq(&self, other: &Self) -> bool {
+        self.value() == other.value()
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({:?})", self.value())
+    }
+}
+
+impl HostRoots {
+    /// Root `value` in a new slot of `roots`, for as long as the handle lives
+    pub(crate) fn root(roots: &Rc<RefCell<HostRoots>>, value: &LiteralValue) -> Handle {
+        let id = {
+            let mut roots = roots.borrow_mut();
+            let id = roots.next_id;
+            roots.next_id += 1;
+            roots.values.insert(id, value.clone());
+            id
+        };
+        Handle(Rc::new(RootSlot { id, value: value.clone(), roots: Rc::downgrade(roots) }))
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &LiteralValue> {
+        self.values.values()
+    }
+
+    /// Whether `handle` was rooted in `roots`
+    pub(crate) fn holds(roots: &Rc<RefCell<HostRoots>>, handle: &Handle) -> bool {
+        Weak::ptr_eq(&handle.0.roots, &Rc::downgrade(roots))
+    }
+}
+
+impl Drop for RootSlot {
+    fn drop(&mut self) {
+        // Nothing to unregister once the engine itself is gone
+        if let Some(roots) = self.roots.upgrade() {
+            roots.borrow_mut().values.remove(&self.id);
+        }
+    }
+}
+
+impl Error {
+    /// A runtime error with just a message, for natives to fail with
+    pub fn runtime(message: String) -> Error {
+        Error::Runtime { message, line: None, stack: Vec::new() }
+    }
+}
+
+impl From<InterpreterError> for Error {
+    fn from(err: InterpreterError) -> Self {
+        if let Some(code) = err.exit_code() {
+            return Error::Exit(code);
+        }
+        if let Some(kind) = err.limit_exceeded() {
+            return Error::Limit { kind, message: err.msg };
+        }
+        let line = match err.span {
+            Some(span) if span.is_known() => { Some(span.line) }
+            _ => { None }
+        };
+        Error::Runtime { message: err.msg, line, stack: err.stack }
+    }
+}
+
+impl From<Error> for InterpreterError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Exit(code) => { InterpreterError::exit(code) }
+            Error::Limit { kind, message } => { InterpreterError::limit(kind, message) }
+            Error::Runtime { message, .. } => { InterpreterError::new(message) }
+            Error::Compile(_) => { InterpreterError::new(err.to_string()) }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Compile(diagnostics) => {
+                for (i, diagnostic) in diagnostics.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{diagnostic}")?;
+                }
+                Ok(())
+            }
+            Error::Runtime { message, line, stack } => {
+                match line {
+                    Some(line) => { write!(f, "[line {line}] Error: {message}")?; }
+                    None => { write!(f, "Error: {message}")?; }
+                }
+                for (frame, repeats) in interpreter::collapse_frames(stack) {
+                    write!(f, "\n  in {}() called from line {}", frame.function, frame.line)?;
+                    if repeats > 0 {
+                        write!(f, "\n  ... repeated {repeats} more times")?;
+                    }
+                }
+                Ok(())
+            }
+            Error::Exit(code) => write!(f, "Script exited with status {code}"),
+            Error::Limit { message, .. } => write!(f, "Error: {message}"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl Diagnostic {
+    fn new(message: &str, span: Span) -> Diagnostic {
+        Diagnostic { message: message.to_string(), line: span.line, column: span.column }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}:{}] Error: {}", self.line, self.column, self.message)
+    }
+}
@@ -1,12 +1,11 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
+use crate::gc::{GcRef, Heap};
 use crate::token::{LiteralValue, Token};
-use std::rc::Rc;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Environment {
     pub(crate) values: HashMap<String, LiteralValue>,
-    enclosing: Option<Rc<RefCell<Environment>>>,
+    pub(crate) enclosing: Option<GcRef>,
 }
 
 impl Environment {
@@ -17,7 +16,7 @@ impl Environment {
         }
     }
 
-    pub fn new_local(enclosing: Rc<RefCell<Environment>>) -> Environment {
+    pub fn new_local(enclosing: GcRef) -> Environment {
         Environment {
             values: HashMap::new(),
             enclosing: Some(enclosing),
@@ -27,13 +26,14 @@ impl Environment {
         self.values.insert(name, value);
     }
 
-    pub fn get(&self, name: &Token) -> Result<LiteralValue, EnvironmentError> {
-        match self.values.get(&name.lexeme) {
+    pub fn get(heap: &Heap, environment: GcRef, name: &Token) -> Result<LiteralValue, EnvironmentError> {
+        let env = heap.env(environment);
+        match env.values.get(&name.lexeme) {
             None => {
-                match &self.enclosing {
+                match env.enclosing {
                     None => { Err(EnvironmentError { msg: format!("Couldn't Find Variable: {}", &name.lexeme) }) }
-                    Some(env) => {
-                        env.borrow().get(&name)
+                    Some(enclosing) => {
+                        Environment::get(heap, enclosing, name)
                     }
                 }
             }
@@ -41,17 +41,18 @@ impl Environment {
         }
     }
 
-    pub fn assign(&mut self, name: &Token, value: LiteralValue) -> Option<EnvironmentError> {
-        match self.values.get_mut(&name.lexeme) {
+    pub fn assign(heap: &mut Heap, environment: GcRef, name: &Token, value: LiteralValue) -> Option<EnvironmentError> {
+        let env = heap.env_mut(environment);
+        match env.values.get_mut(&name.lexeme) {
             None => {
-                match &mut self.enclosing {
+                match env.enclosing {
                     None => {
                         Some(EnvironmentError {
                             msg: format!("Variable {} not yet declared", name.lexeme)
                         })
                     }
-                    Some(env) => {
-                        env.borrow_mut().assign(name, value)
+                    Some(enclosing) => {
+                        Environment::assign(heap, enclosing, name, value)
                     }
                 }
             }
@@ -61,34 +62,55 @@ impl Environment {
             }
         }
     }
-    
-    pub fn full_clone(environment: &Rc<RefCell<Environment>>, 
-                  cur_env_copy: Option<Environment>)->Environment {
-        // Create a new environment if needed (for recursion)
-        let mut new_env = match cur_env_copy {
-            None => {Environment::new_global()}
-            Some(env) => {env}
-        };
-        
-        // Get the values from the passed in reference environment
-        let environment_values = environment.borrow().values.clone();
-        
-        for (key, value) in &environment_values {
-            new_env.define(key.clone(), value.clone())
+
+    /// The global environment at the end of the chain, each module has its own
+    pub fn root(heap: &Heap, environment: GcRef) -> GcRef {
+        let mut current = environment;
+        while let Some(enclosing) = heap.env(current).enclosing {
+            current = enclosing;
         }
-        
-        
-        // If there is an enclosing environment, copy it into this 
-        // function as well
-         match &environment.borrow().enclosing {
-            None => {new_env}
-            Some(env) => {
-                Environment::full_clone(env, Some(new_env))
+        current
+    }
+
+    /// Walk up a fixed number of enclosing environments
+    pub fn ancestor(heap: &Heap, environment: GcRef, distance: usize) -> Result<GcRef, EnvironmentError> {
+        let mut current = environment;
+        for _ in 0..distance {
+            current = match heap.env(current).enclosing {
+                None => {
+                    return Err(EnvironmentError {
+                        msg: "Variable resolved to a missing scope".to_string()
+                    });
+                }
+                Some(enclosing) => { enclosing }
+            };
+        }
+        Ok(current)
+    }
+
+    /// Get a variable from the environment exactly `distance` scopes up, as
+    /// computed by the resolver
+    pub fn get_at(heap: &Heap, environment: GcRef, distance: usize,
+                  name: &Token) -> Result<LiteralValue, EnvironmentError> {
+        let ancestor = Environment::ancestor(heap, environment, distance)?;
+        match heap.env(ancestor).values.get(&name.lexeme) {
+            None => { Err(EnvironmentError { msg: format!("Couldn't Find Variable: {}", &name.lexeme) }) }
+            Some(val) => { Ok(val.clone()) }
+        }
+    }
+
+    pub fn assign_at(heap: &mut Heap, environment: GcRef, distance: usize,
+                     name: &Token, value: LiteralValue) -> Option<EnvironmentError> {
+        match Environment::ancestor(heap, environment, distance) {
+            Ok(ancestor) => {
+                heap.env_mut(ancestor).define(name.lexeme.clone(), value);
+                None
             }
+            Err(err) => { Some(err) }
         }
     }
 }
 
 pub struct EnvironmentError {
     pub msg: String,
-}
\ No newline at end of file
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::env;
+
+use crate::environment::Environment;
+use crate::lox_class::LoxClass;
+use crate::lox_map::LoxMap;
+use crate::token::{FunctionEnum, LiteralValue};
+
+/// Handle to an object owned by the garbage collected heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GcRef(usize);
+
+pub enum HeapObject {
+    Environment(Environment),
+    Fields(HashMap<String, LiteralValue>),
+    List(Vec<LiteralValue>),
+    Map(LoxMap),
+}
+
+/// Tuning knobs for the collector, thresholds are counted in heap objects
+#[derive(Debug, Clone, Copy)]
+pub struct GcConfig {
+    /// Number of live objects before the first collection
+    pub initial_threshold: usize,
+    /// After a collection the next one happens once the heap has grown by this factor
+    pub growth_factor: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeapStats {
+    pub live_objects: usize,
+    pub collections: usize,
+    pub total_freed: usize,
+    pub next_collection: usize,
+}
+
+/// Mark and sweep heap holding environments, instance fields, lists and maps,
+/// which are the objects that can form reference cycles
+pub struct Heap {
+    objects: Vec<Option<HeapObject>>,
+    marks: Vec<bool>,
+    free_slots: Vec<usize>,
+    gray: Vec<GcRef>,
+    config: GcConfig,
+    stats: HeapStats,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        GcConfig {
+            initial_threshold: 1024,
+            growth_factor: 2.0,
+        }
+    }
+}
+
+impl GcConfig {
+    /// Read overrides from LOXRS_GC_THRESHOLD and LOXRS_GC_GROWTH
+    pub fn from_env() -> GcConfig {
+        let mut config = GcConfig::default();
+        if let Some(threshold) = env::var("LOXRS_GC_THRESHOLD").ok().and_then(|v| v.parse().ok()) {
+            config.initial_threshold = threshold;
+        }
+        if let Some(growth) = env::var("LOXRS_GC_GROWTH").ok().and_then(|v| v.parse::<f64>().ok()) {
+            if growth >= 1.0 {
+                config.growth_factor = growth;
+            }
+        }
+        config
+    }
+}
+
+impl Heap {
+    pub fn new(config: GcConfig) -> Heap {
+        Heap {
+            objects: Vec::new(),
+            marks: Vec::new(),
+            free_slots: Vec::new(),
+            gray: Vec::new(),
+            config,
+            stats: HeapStats {
+                next_collection: config.initial_threshold,
+                ..HeapStats::default()
+            },
+        }
+    }
+
+    pub fn alloc_env(&mut self, environment: Environment) -> GcRef {
+        self.alloc(HeapObject::Environment(environment))
+    }
+
+    pub fn alloc_fields(&mut self, fields: HashMap<String, LiteralValue>) -> GcRef {
+        self.alloc(HeapObject::Fields(fields))
+    }
+
+    pub fn alloc_list(&mut self, elements: Vec<LiteralValue>) -> GcRef {
+        self.alloc(HeapObject::List(elements))
+    }
+
+    pub fn alloc_map(&mut self, map: LoxMap) -> GcRef {
+        self.alloc(HeapObject::Map(map))
+    }
+
+    fn alloc(&mut self, object: HeapObject) -> GcRef {
+        self.stats.live_objects += 1;
+        match self.free_slots.pop() {
+            Some(slot) => {
+                self.objects[slot] = Some(object);
+                GcRef(slot)
+            }
+            None => {
+                self.objects.push(Some(object));
+                self.marks.push(false);
+                GcRef(self.objects.len() - 1)
+            }
+        }
+    }
+
+    pub fn env(&self, reference: GcRef) -> &Environment {
+        match &self.objects[reference.0] {
+            Some(HeapObject::Environment(environment)) => { environment }
+            _ => { panic!("Heap slot {} is not a live environment", reference.0) }
+        }
+    }
+
+    pub fn env_mut(&mut self, reference: GcRef) -> &mut Environment {
+        match &mut self.objects[reference.0] {
+            Some(HeapObject::Environment(environment)) => { environment }
+            _ => { panic!("Heap slot {} is not a live environment", reference.0) }
+        }
+    }
+
+    pub fn fields(&self, reference: GcRef) -> &HashMap<String, LiteralValue> {
+        match &self.objects[reference.0] {
+            Some(HeapObject::Fields(fields)) => { fields }
+            _ => { panic!("Heap slot {} is not a live instance", reference.0) }
+        }
+    }
+
+    pub fn fields_mut(&mut self, reference: GcRef) -> &mut HashMap<String, LiteralValue> {
+        match &mut self.objects[reference.0] {
+            Some(HeapObject::Fields(fields)) => { fields }
+            _ => { panic!("Heap slot {} is not a live instance", reference.0) }
+        }
+    }
+
+    pub fn list(&self, reference: GcRef) -> &Vec<LiteralValue> {
+        match &self.objects[reference.0] {
+            Some(HeapObject::List(elements)) => { elements }
+            _ => { panic!("Heap slot {} is not a live list", reference.0) }
+        }
+    }
+
+    pub fn list_mut(&mut self, reference: GcRef) -> &mut Vec<LiteralValue> {
+        match &mut self.objects[reference.0] {
+            Some(HeapObject::List(elements)) => { elements }
+            _ => { panic!("Heap slot {} is not a live list", reference.0) }
+        }
+    }
+
+    pub fn map(&self, reference: GcRef) -> &LoxMap {
+        match &self.objects[reference.0] {
+            Some(HeapObject::Map(map)) => { map }
+            _ => { panic!("Heap slot {} is not a live map", reference.0) }
+        }
+    }
+
+    pub fn map_mut(&mut self, reference: GcRef) -> &mut LoxMap {
+        match &mut self.objects[reference.0] {
+            Some(HeapObject::Map(map)) => { map }
+            _ => { panic!("Heap slot {} is not a live map", reference.0) }
+        }
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.stats.live_objects >= self.stats.next_collection
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    /// Free every object not reachable from the given roots, returning how
+    /// many objects were reclaimed
+    pub fn collect(&mut self, root_refs: &[GcRef], root_values: &[LiteralValue]) -> usize {
+        for reference in root_refs {
+            self.mark_ref(*reference);
+        }
+        for value in root_values {
+            self.mark_value(value);
+        }
+        self.trace_references();
+        let freed = self.sweep();
+
+        self.stats.collections += 1;
+        self.stats.total_freed += freed;
+        let grown = (self.stats.live_objects as f64 * self.config.growth_factor) as usize;
+        self.stats.next_collection = grown.max(self.config.initial_threshold);
+        freed
+    }
+
+    fn mark_ref(&mut self, reference: GcRef) {
+        if self.marks[reference.0] {
+            return;
+        }
+        self.marks[reference.0] = true;
+        self.gray.push(reference);
+    }
+
+    fn mark_value(&mut self, value: &LiteralValue) {
+        let mut children = Vec::new();
+        Heap::value_refs(value, &mut children);
+        for child in children {
+            self.mark_ref(child);
+        }
+    }
+
+    /// Heap objects directly referenced by a value
+    fn value_refs(value: &LiteralValue, refs: &mut Vec<GcRef>) {
+        match value {
+            LiteralValue::Function(FunctionEnum::User(function)) => {
+                refs.push(function.closure);
+            }
+            LiteralValue::Class(class) => {
+                Heap::class_refs(class, refs);
+            }
+            LiteralValue::Instance(instance) => {
+                refs.push(instance.fields);
+                Heap::class_refs(&instance.class, refs);
+            }
+            LiteralValue::List(list) => {
+                refs.push(*list);
+            }
+            LiteralValue::Map(map) => {
+                refs.push(*map);
+            }
+            _ => {}
+        }
+    }
+
+    fn class_refs(class: &LoxClass, refs: &mut Vec<GcRef>) {
+        for method in class.methods.values() {
+            refs.push(method.closure);
+        }
+        if let Some(superclass) = &class.superclass {
+            Heap::class_refs(superclass, refs);
+        }
+    }
+
+    fn trace_references(&mut self) {
+        let mut children = Vec::new();
+        while let Some(reference) = self.gray.pop() {
+            match &self.objects[reference.0] {
+                Some(HeapObject::Environment(environment)) => {
+                    if let Some(enclosing) = environment.enclosing {
+                        children.push(enclosing);
+                    }
+                    for value in environment.values.values() {
+                        Heap::value_refs(value, &mut children);
+                    }
+                }
+                Some(HeapObject::Fields(fields)) => {
+                    for value in fields.values() {
+                        Heap::value_refs(value, &mut children);
+                    }
+                }
+                Some(HeapObject::List(elements)) => {
+                    for value in elements {
+                        Heap::value_refs(value, &mut children);
+                    }
+                }
+                Some(HeapObject::Map(map)) => {
+                    // Keys are always plain values, only the values can hold references
+                    for (_, value) in map.entries() {
+                        Heap::value_refs(value, &mut children);
+                    }
+                }
+                None => {}
+            }
+            for child in children.drain(..) {
+                self.mark_ref(child);
+            }
+        }
+    }
+
+    fn sweep(&mut self) -> usize {
+        let mut freed = 0;
+        for slot in 0..self.objects.len() {
+            if self.marks[slot] {
+                self.marks[slot] = false;
+            } else if self.objects[slot].is_some() {
+                self.objects[slot] = None;
+                self.free_slots.push(slot);
+                freed += 1;
+            }
+        }
+        self.stats.live_objects -= freed;
+        freed
+    }
+}
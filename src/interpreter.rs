@@ -1,46 +1,204 @@
 use std::cell::{Ref, RefCell};
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::io;
 use std::io::Write;
+use std::fs;
+use std::path::Path;
 use std::ops::Deref;
 use std::rc::Rc;
 
 use crate::ast::{Expr, Stmt, Visitor};
-use crate::environment::Environment;
+use crate::engine::{self, Handle, HostRoots, Value};
+use crate::environment::{Environment, EnvironmentError};
+use crate::gc::{GcConfig, GcRef, Heap};
+use crate::lox_budget::{Budget, InterruptHandle, LimitExceeded, Limits};
 use crate::lox_callable::LoxCallable;
-use crate::token::{FunctionEnum, LiteralValue, TokenType, UserDefinedFunction};
-use crate::token::NativeFunctions::Clock;
+use crate::lox_class::{LoxClass, LoxInstance};
+use crate::lox_host;
+use crate::lox_io;
+use crate::lox_json;
+use crate::lox_list;
+use crate::lox_map::{self, LoxMap, MapKey};
+use crate::lox_math;
+use crate::lox_module::{self, ModuleLoader, SourceFile};
+use crate::lox_native::{Args, Arity, NativeBody, NativeRegistry};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Lexer;
+use crate::token::{FunctionEnum, LiteralValue, Span, Token, TokenType, UserDefinedFunction};
 
 pub struct Interpreter {
-    pub environment: Rc<RefCell<Environment>>,
-    pub globals: Rc<RefCell<Environment>>,
+    pub environment: GcRef,
+    pub globals: GcRef,
+    pub heap: Heap,
+    // Environments saved by enclosing blocks and calls, still live while inner code runs
+    env_stack: Vec<GcRef>,
+    // Values held by partially evaluated expressions, kept alive during collection
+    temp_roots: Vec<LiteralValue>,
+    // Line of each call expression currently being executed
+    call_lines: Vec<i32>,
+    // Class of the values runtime errors are turned into when caught
+    error_class: Rc<LoxClass>,
+    modules: ModuleLoader,
+    // Class of the values `import ... as name` binds
+    module_class: Rc<LoxClass>,
+    // Set by the host to stop scripts from reading or writing files
+    sandboxed: bool,
+    // Command line arguments after the script path, returned by `args()`
+    script_args: Vec<String>,
+    // Functions and objects an embedding host holds handles to, shared with the handles
+    host_roots: Rc<RefCell<HostRoots>>,
+    // Natives written in Rust closures, defined in every global environment
+    natives: NativeRegistry,
+    // Step, call depth and time limits, checked as statements run
+    budget: Budget,
 }
 
+/// A Lox function a runtime error unwound through, and the line it was called from
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    pub function: String,
+    pub line: i32,
+}
+
+/// Pair each frame of a traceback with how many times it repeats right after
+/// itself, so deep recursion prints as one frame instead of thousands
+pub(crate) fn collapse_frames(stack: &[StackFrame]) -> Vec<(&StackFrame, usize)> {
+    let mut collapsed: Vec<(&StackFrame, usize)> = Vec::new();
+    for frame in stack {
+        match collapsed.last_mut() {
+            Some((last, repeats)) if last.function == frame.function && last.line == frame.line => { *repeats += 1; }
+            _ => { collapsed.push((frame, 0)); }
+        }
+    }
+    collapsed
+}
+
+/// A runtime error or a value thrown by the script, as opposed to control
+/// flow leaving a statement early
 pub struct InterpreterError {
     pub(crate) msg: String,
-    pub(crate) returning: bool,
-    pub(crate) value: Option<LiteralValue>,
+    // Innermost expression or statement that failed, filled in while unwinding
+    pub(crate) span: Option<Span>,
+    // Calls the error escaped from, innermost first
+    pub(crate) stack: Vec<StackFrame>,
+    // Set by `throw`, `exit()`, exceeded limits and imported files that don't
+    // compile, other errors raised by the interpreter have none
+    pub(crate) raised: Option<Raised>,
+}
+
+/// What the script raised when an error didn't come from the interpreter
+pub enum Raised {
+    Thrown(LiteralValue),
+    // `exit()` unwinds like an error, but can't be caught
+    Exit(u8),
+    // Neither can a run being stopped for going over its budget
+    Limit(LimitExceeded),
+    // A scan, parse or resolve error in an imported file
+    Static,
+}
+
+/// Why executing a statement stopped before reaching its end
+pub enum Unwind {
+    Return(LiteralValue),
+    Break,
+    Continue,
+    Error(InterpreterError),
+}
+
+impl InterpreterError {
+    pub fn new(msg: String) -> InterpreterError {
+        InterpreterError {
+            msg,
+            span: None,
+            stack: Vec::new(),
+            raised: None,
+        }
+    }
+
+    pub fn thrown(msg: String, value: LiteralValue) -> InterpreterError {
+        InterpreterError {
+            raised: Some(Raised::Thrown(value)),
+            ..InterpreterError::new(msg)
+        }
+    }
+
+    pub fn exit(code: u8) -> InterpreterError {
+        InterpreterError {
+            raised: Some(Raised::Exit(code)),
+            ..InterpreterError::new(format!("Exited with status {code}."))
+        }
+    }
+
+    pub fn limit(kind: LimitExceeded, msg: String) -> InterpreterError {
+        InterpreterError {
+            raised: Some(Raised::Limit(kind)),
+            ..InterpreterError::new(msg)
+        }
+    }
+
+    /// Which limit stopped the script, if that is why it failed
+    pub fn limit_exceeded(&self) -> Option<LimitExceeded> {
+        match self.raised {
+            Some(Raised::Limit(kind)) => { Some(kind) }
+            _ => { None }
+        }
+    }
+
+    /// Whether `catch` may handle the error, everything but `exit()` and exceeded limits
+    pub fn is_catchable(&self) -> bool {
+        !matches!(self.raised, Some(Raised::Exit(_)) | Some(Raised::Limit(_)))
+    }
+
+    /// Whether the error was found before the code it is in ran
+    pub fn is_static(&self) -> bool {
+        matches!(self.raised, Some(Raised::Static))
+    }
+
+    pub fn exit_code(&self) -> Option<u8> {
+        match self.raised {
+            Some(Raised::Exit(code)) => { Some(code) }
+            _ => { None }
+        }
+    }
 }
 
-impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
+impl From<InterpreterError> for Unwind {
+    fn from(err: InterpreterError) -> Self {
+        Unwind::Error(err)
+    }
+}
+
+impl Visitor<Result<LiteralValue, InterpreterError>, Result<LiteralValue, Unwind>> for Interpreter {
     fn visit_expr(&mut self, expr: &mut Expr) -> Result<LiteralValue, InterpreterError> {
         match expr {
-            Expr::Assign { name, value } => {
+            Expr::Assign { name, value, depth } => {
                 let value: LiteralValue = self.evaluate(value)?;
-                match self.environment.borrow_mut().assign(name, value.clone()) {
+                let assign_result = match depth {
+                    None => {
+                        let globals = self.current_globals();
+                        Environment::assign(&mut self.heap, globals, name, value.clone())
+                    }
+                    Some(distance) => {
+                        Environment::assign_at(&mut self.heap, self.environment, *distance, name, value.clone())
+                    }
+                };
+                match assign_result {
                     None => { Ok(value) }
                     Some(err) => {
-                        Err(InterpreterError {
-                            msg: err.msg,
-                            returning: false,
-                            value: None,
-                        })
+                        Err(InterpreterError::new(err.msg))
                     }
                 }
             }
             Expr::Binary { left, operator, right } => {
                 let lhs = self.evaluate(left)?;
-                let rhs = self.evaluate(right)?;
+                self.temp_roots.push(lhs.clone());
+                let rhs = self.evaluate(right);
+                self.temp_roots.pop();
+                let rhs = rhs?;
+                // NaN is unordered, so comparing with it is false, apart from !=
+                let unordered = [&lhs, &rhs].iter().any(|value| matches!(value, LiteralValue::NumValue(num) if num.is_nan()));
                 match operator.token_type {
                     TokenType::Minus => {
                         match lhs - rhs {
@@ -48,11 +206,7 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                                 Ok(literal_value)
                             }
                             Err(_) => {
-                                Err(InterpreterError {
-                                    msg: "Invalid Subtraction".to_string(),
-                                    returning: false,
-                                    value: None,
-                                })
+                                Err(InterpreterError::new("Invalid Subtraction".to_string()))
                             }
                         }
                     }
@@ -62,11 +216,7 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                                 Ok(literal_value)
                             }
                             Err(_) => {
-                                Err(InterpreterError {
-                                    msg: "Invalid Addition".to_string(),
-                                    returning: false,
-                                    value: None,
-                                })
+                                Err(InterpreterError::new("Invalid Addition".to_string()))
                             }
                         }
                     }
@@ -76,11 +226,7 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                                 Ok(literal_value)
                             }
                             Err(_) => {
-                                Err(InterpreterError {
-                                    msg: "Invalid Division".to_string(),
-                                    returning: false,
-                                    value: None,
-                                })
+                                Err(InterpreterError::new("Invalid Division".to_string()))
                             }
                         }
                     }
@@ -90,23 +236,21 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                                 Ok(literal_value)
                             }
                             Err(_) => {
-                                Err(InterpreterError {
-                                    msg: "".to_string(),
-                                    returning: false,
-                                    value: None,
-                                })
+                                Err(InterpreterError::new("".to_string()))
                             }
                         }
                     }
                     TokenType::BangEqual => {
+                        if let Some(equal) = Interpreter::identity_equality(&lhs, &rhs) {
+                            return Ok(if equal { LiteralValue::False } else { LiteralValue::True });
+                        }
                         match LiteralValue::partial_cmp(&lhs, &rhs) {
+                            None if unordered => {
+                                Ok(LiteralValue::True)
+                            }
                             None => {
                                 Err(
-                                    InterpreterError {
-                                        msg: "Invalid Inequality Comparison".to_string(),
-                                        returning: false,
-                                        value: None,
-                                    }
+                                    InterpreterError::new("Invalid Inequality Comparison".to_string())
                                 )
                             }
                             Some(cmp) => {
@@ -125,14 +269,16 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                         }
                     }
                     TokenType::EqualEqual => {
+                        if let Some(equal) = Interpreter::identity_equality(&lhs, &rhs) {
+                            return Ok(if equal { LiteralValue::True } else { LiteralValue::False });
+                        }
                         match LiteralValue::partial_cmp(&lhs, &rhs) {
+                            None if unordered => {
+                                Ok(LiteralValue::False)
+                            }
                             None => {
                                 Err(
-                                    InterpreterError {
-                                        msg: "Invalid Equality Comparison".to_string(),
-                                        returning: false,
-                                        value: None,
-                                    }
+                                    InterpreterError::new("Invalid Equality Comparison".to_string())
                                 )
                             }
                             Some(cmp) => {
@@ -152,13 +298,12 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                     }
                     TokenType::Greater => {
                         match LiteralValue::partial_cmp(&lhs, &rhs) {
+                            None if unordered => {
+                                Ok(LiteralValue::False)
+                            }
                             None => {
                                 Err(
-                                    InterpreterError {
-                                        msg: "Invalid Greater Comparison".to_string(),
-                                        returning: false,
-                                        value: None,
-                                    }
+                                    InterpreterError::new("Invalid Greater Comparison".to_string())
                                 )
                             }
                             Some(cmp) => {
@@ -178,13 +323,12 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                     }
                     TokenType::GreaterEqual => {
                         match LiteralValue::partial_cmp(&lhs, &rhs) {
+                            None if unordered => {
+                                Ok(LiteralValue::False)
+                            }
                             None => {
                                 Err(
-                                    InterpreterError {
-                                        msg: "Invalid Greater/Equal Comparison".to_string(),
-                                        returning: false,
-                                        value: None,
-                                    }
+                                    InterpreterError::new("Invalid Greater/Equal Comparison".to_string())
                                 )
                             }
                             Some(cmp) => {
@@ -204,13 +348,12 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                     }
                     TokenType::Less => {
                         match LiteralValue::partial_cmp(&lhs, &rhs) {
+                            None if unordered => {
+                                Ok(LiteralValue::False)
+                            }
                             None => {
                                 Err(
-                                    InterpreterError {
-                                        msg: "Invalid Less Comparison".to_string(),
-                                        returning: false,
-                                        value: None,
-                                    }
+                                    InterpreterError::new("Invalid Less Comparison".to_string())
                                 )
                             }
                             Some(cmp) => {
@@ -230,13 +373,12 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                     }
                     TokenType::LessEqual => {
                         match LiteralValue::partial_cmp(&lhs, &rhs) {
+                            None if unordered => {
+                                Ok(LiteralValue::False)
+                            }
                             None => {
                                 Err(
-                                    InterpreterError {
-                                        msg: "Invalid Less/Equal Comparison".to_string(),
-                                        returning: false,
-                                        value: None,
-                                    }
+                                    InterpreterError::new("Invalid Less/Equal Comparison".to_string())
                                 )
                             }
                             Some(cmp) => {
@@ -258,11 +400,7 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                         match lhs & rhs {
                             Ok(value) => { Ok(value) }
                             Err(_) => {
-                                Err(InterpreterError {
-                                    msg: "Invalid AND Operation".to_string(),
-                                    returning: false,
-                                    value: None,
-                                })
+                                Err(InterpreterError::new("Invalid AND Operation".to_string()))
                             }
                         }
                     }
@@ -270,64 +408,94 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                         match lhs | rhs {
                             Ok(value) => { Ok(value) }
                             Err(_) => {
-                                Err(InterpreterError {
-                                    msg: "Invalid OR Operation".to_string(),
-                                    returning: false,
-                                    value: None,
-                                })
+                                Err(InterpreterError::new("Invalid OR Operation".to_string()))
                             }
                         }
                     }
                     _ => {
-                        Err(InterpreterError {
-                            msg: "Invalid Binary Operator".to_string(),
-                            returning: false,
-                            value: None,
-                        })
+                        Err(InterpreterError::new("Invalid Binary Operator".to_string()))
                     }
                 }
             }
             Expr::Call { callee, paren, arguments } => {
                 let callee: LiteralValue = self.evaluate(callee)?;
 
-                let mut args: Vec<LiteralValue> = Vec::new();
-                for argument in arguments {
-                    args.push(self.evaluate(argument)?);
-                }
+                self.temp_roots.push(callee.clone());
+                let args = self.evaluate_arguments(arguments);
+                self.temp_roots.pop();
+                let args: Vec<LiteralValue> = args?;
 
-                let function = match callee {
-                    LiteralValue::Function(fun) => {
-                        if args.len() as u8 != fun.arity()? {
-                            return Err(InterpreterError {
-                                msg: "Incorrect number of arguments".to_string(),
-                                returning: false,
-                                value: None,
-                            });
-                        }
-                        fun.call(self, args)
+                self.call_value(callee, args, paren.line)
+            }
+            Expr::Get { object, name } => {
+                match self.evaluate(object)? {
+                    LiteralValue::Instance(instance) => {
+                        instance.get(name, &mut self.heap)
                     }
-                    _ => Err(InterpreterError {
-                        msg: "Tried to call non-callable".to_string(),
-                        returning: false,
-                        value: None,
-                    })
-                };
-
-                function
+                    LiteralValue::Host(object) => {
+                        lox_host::get(self, &object, name)
+                    }
+                    _ => {
+                        Err(InterpreterError::new("Only instances have properties.".to_string()))
+                    }
+                }
+            }
+            Expr::Grouping { expression, .. } => {
+                self.evaluate(expression)
             }
-            Expr::Get { .. } => {
-                Err(InterpreterError {
-                    msg: "Not Implemented Yet".to_string(),
-                    returning: false,
-                    value: None,
+            Expr::Index { object, index, .. } => {
+                let container = self.evaluate(object)?;
+                if !matches!(container, LiteralValue::List(_) | LiteralValue::Map(_)) {
+                    return Err(InterpreterError::new("Only lists and maps can be indexed.".to_string()));
+                }
+                self.temp_roots.push(container.clone());
+                let key = self.evaluate(index);
+                self.temp_roots.pop();
+                let key = key?;
+                let result = match container {
+                    LiteralValue::Map(map) => { lox_map::get_key(&self.heap, map, &key) }
+                    LiteralValue::List(list) => { lox_list::get_index(&self.heap, list, &key) }
+                    _ => { unreachable!() }
+                };
+                result.map_err(|mut err| {
+                    err.span = Some(index.span());
+                    err
                 })
             }
-            Expr::Grouping { expression } => {
-                self.evaluate(expression)
+            Expr::Lambda { declaration, .. } => {
+                Ok(LiteralValue::Function(FunctionEnum::User(UserDefinedFunction {
+                    closure: self.environment,
+                    declaration: Rc::new(RefCell::new(declaration.as_ref().clone())),
+                    is_initializer: false,
+                })))
             }
-            Expr::Literal { value } => {
+            Expr::List { elements, .. } => {
+                let elements: Vec<LiteralValue> = self.evaluate_arguments(elements)?;
+                Ok(LiteralValue::List(self.heap.alloc_list(elements)))
+            }
+            Expr::Literal { value, .. } => {
                 Ok(value.clone())
             }
+            Expr::Map { entries, .. } => {
+                // Keys and values are rooted as they are evaluated since any
+                // of them may allocate and trigger a collection
+                let roots_base = self.temp_roots.len();
+                let mut map = LoxMap::new();
+                for (key, value) in entries.iter_mut() {
+                    let pair = self.evaluate_map_entry(key, value);
+                    let (key, value) = match pair {
+                        Ok(pair) => { pair }
+                        Err(err) => {
+                            self.temp_roots.truncate(roots_base);
+                            return Err(err);
+                        }
+                    };
+                    self.temp_roots.push(value.clone());
+                    map.insert(key, value);
+                }
+                self.temp_roots.truncate(roots_base);
+                Ok(LiteralValue::Map(self.heap.alloc_map(map)))
+            }
             Expr::Logical { left, operator, right } => {
                 let left: LiteralValue = self.evaluate(left)?;
 
@@ -342,26 +510,82 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                 }
                 self.evaluate(right)
             }
-            Expr::Set { .. } => {
-                Err(InterpreterError {
-                    msg: "Not Implemented Yet".to_string(),
-                    returning: false,
-                    value: None,
-                })
+            Expr::Set { object, name, value } => {
+                let instance = match self.evaluate(object)? {
+                    LiteralValue::Instance(instance) => { instance }
+                    LiteralValue::Host(object) => {
+                        let value = self.evaluate(value)?;
+                        lox_host::set(self, &object, name, &value)?;
+                        return Ok(value);
+                    }
+                    _ => {
+                        return Err(InterpreterError::new("Only instances have fields.".to_string()));
+                    }
+                };
+                self.temp_roots.push(LiteralValue::Instance(instance.clone()));
+                let value = self.evaluate(value);
+                self.temp_roots.pop();
+                let value: LiteralValue = value?;
+                instance.set(name, value.clone(), &mut self.heap);
+                Ok(value)
             }
-            Expr::Super { .. } => {
-                Err(InterpreterError {
-                    msg: "Not Implemented Yet".to_string(),
-                    returning: false,
-                    value: None,
-                })
+            Expr::SetIndex { object, index, value, .. } => {
+                let container = self.evaluate(object)?;
+                if !matches!(container, LiteralValue::List(_) | LiteralValue::Map(_)) {
+                    return Err(InterpreterError::new("Only lists and maps can be indexed.".to_string()));
+                }
+                self.temp_roots.push(container.clone());
+                let key = self.evaluate(index);
+                let new_value = match key {
+                    Ok(_) => { self.evaluate(value) }
+                    Err(_) => { Ok(LiteralValue::None) }
+                };
+                self.temp_roots.pop();
+                let key = key?;
+                let new_value = new_value?;
+                let result = match container {
+                    LiteralValue::Map(map) => { lox_map::set_key(&mut self.heap, map, &key, new_value.clone()) }
+                    LiteralValue::List(list) => { lox_list::set_index(&mut self.heap, list, &key, new_value.clone()) }
+                    _ => { unreachable!() }
+                };
+                result.map_err(|mut err| {
+                    err.span = Some(index.span());
+                    err
+                })?;
+                Ok(new_value)
             }
-            Expr::This { .. } => {
-                Err(InterpreterError {
-                    msg: "Not Implemented Yet".to_string(),
-                    returning: false,
-                    value: None,
-                })
+            Expr::Super { keyword, method, depth } => {
+                let superclass = match self.look_up_variable(keyword, depth) {
+                    Ok(LiteralValue::Class(class)) => { class }
+                    _ => {
+                        return Err(InterpreterError::new("Can't use 'super' in a class with no superclass.".to_string()));
+                    }
+                };
+                // `this` is always bound in the scope just inside the one binding `super`
+                let this_token = Token::spanning(TokenType::This, "this".to_string(), LiteralValue::None, keyword.span);
+                let this_depth = depth.map(|distance| distance.saturating_sub(1));
+                let instance = match self.look_up_variable(&this_token, &this_depth) {
+                    Ok(LiteralValue::Instance(instance)) => { instance }
+                    _ => {
+                        return Err(InterpreterError::new("Can't use 'super' outside of a class.".to_string()));
+                    }
+                };
+                match superclass.find_method(&method.lexeme) {
+                    Some(function) => {
+                        Ok(LiteralValue::Function(FunctionEnum::User(function.bind(instance, &mut self.heap))))
+                    }
+                    None => {
+                        Err(InterpreterError::new(format!("Undefined property '{}'.", method.lexeme)))
+                    }
+                }
+            }
+            Expr::This { keyword, depth } => {
+                match self.look_up_variable(keyword, depth) {
+                    Ok(val) => { Ok(val) }
+                    Err(_) => {
+                        Err(InterpreterError::new("Can't use 'this' outside of a class.".to_string()))
+                    }
+                }
             }
             Expr::Unary { operator, right } => {
                 let rhs = self.evaluate(right);
@@ -374,11 +598,7 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                                         Ok(new_value)
                                     }
                                     Err(_) => {
-                                        Err(InterpreterError {
-                                            msg: "Invalid Negative Operation".to_string(),
-                                            returning: false,
-                                            value: None,
-                                        })
+                                        Err(InterpreterError::new("Invalid Negative Operation".to_string()))
                                     }
                                 }
                             }
@@ -388,69 +608,106 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                                         Ok(new_value)
                                     }
                                     Err(_) => {
-                                        Err(InterpreterError {
-                                            msg: "Invalid Not Operation".to_string(),
-                                            returning: false,
-                                            value: None,
-                                        })
+                                        Err(InterpreterError::new("Invalid Not Operation".to_string()))
                                     }
                                 }
                             }
-                            _ => Err(InterpreterError {
-                                msg: "Invalid Unary Operator".to_string(),
-                                returning: false,
-                                value: None,
-                            })
+                            _ => Err(InterpreterError::new("Invalid Unary Operator".to_string()))
                         }
                     }
-                    Err(err) => {
-                        Err(InterpreterError {
-                            msg:
-                            err.msg,
-                            returning: false,
-                            value: None,
-                        })
-                    }
+                    Err(err) => { Err(err) }
                 }
             }
-            Expr::Variable { name } => {
-                match self.environment.borrow().get(name) {
+            Expr::Variable { name, depth } => {
+                match self.look_up_variable(name, depth) {
                     Ok(val) => { Ok(val) }
                     Err(_) => {
-                        Err(InterpreterError {
-                            msg: format!("{} not defined", name.lexeme),
-                            returning: false,
-                            value: None,
-                        })
+                        Err(InterpreterError::new(format!("{} not defined", name.lexeme)))
                     }
                 }
             }
         }
     }
 
-    fn visit_stmt(&mut self, stmt: &mut Stmt) -> Result<LiteralValue, InterpreterError> {
+    fn visit_stmt(&mut self, stmt: &mut Stmt) -> Result<LiteralValue, Unwind> {
         match stmt {
             Stmt::Block { statements } => {
-                let _ = self.execute_block(statements, Rc::new(RefCell::new(Environment::new_local(self.environment.clone()))))?;
+                let block_env = self.heap.alloc_env(Environment::new_local(self.environment));
+                let _ = self.execute_block(statements, block_env)?;
                 return Ok(LiteralValue::None);
             }
-            Stmt::Class { .. } => {
-                Err(InterpreterError {
-                    msg: "Not Implemented Yet".to_string(),
-                    returning: false,
-                    value: None,
-                })
+            Stmt::Break { .. } => {
+                Err(Unwind::Break)
+            }
+            Stmt::Class { name, superclass, methods } => {
+                let superclass: Option<Rc<LoxClass>> = match superclass {
+                    None => { None }
+                    Some(superclass_expr) => {
+                        if let Expr::Variable { name: superclass_name, .. } = superclass_expr.as_ref() {
+                            if superclass_name.lexeme == name.lexeme {
+                                return Err(InterpreterError::new("A class can't inherit from itself.".to_string()).into());
+                            }
+                        }
+                        match self.evaluate(superclass_expr)? {
+                            LiteralValue::Class(class) => { Some(class) }
+                            _ => {
+                                let mut err = InterpreterError::new("Superclass must be a class.".to_string());
+                                err.span = Some(superclass_expr.span());
+                                return Err(err.into());
+                            }
+                        }
+                    }
+                };
+
+                self.heap.env_mut(self.environment).define(name.lexeme.clone(), LiteralValue::None);
+
+                // Methods of a subclass close over an environment binding `super`
+                let previous: GcRef = self.environment;
+                if let Some(class) = &superclass {
+                    let mut super_env = Environment::new_local(self.environment);
+                    super_env.define("super".to_string(), LiteralValue::Class(class.clone()));
+                    self.environment = self.heap.alloc_env(super_env);
+                }
+
+                let mut class_methods: HashMap<String, UserDefinedFunction> = HashMap::new();
+                for method in methods {
+                    if let Stmt::Function { name: method_name, .. } = method.as_ref() {
+                        let function = UserDefinedFunction {
+                            closure: self.environment,
+                            declaration: Rc::new(RefCell::new(method.as_ref().clone())),
+                            is_initializer: method_name.lexeme == "init",
+                        };
+                        class_methods.insert(method_name.lexeme.clone(), function);
+                    }
+                }
+
+                self.environment = previous;
+
+                let class = LiteralValue::Class(Rc::new(LoxClass::new(name.lexeme.clone(), superclass, class_methods)));
+                match Environment::assign(&mut self.heap, self.environment, name, class) {
+                    None => { Ok(LiteralValue::None) }
+                    Some(err) => {
+                        Err(InterpreterError::new(err.msg).into())
+                    }
+                }
+            }
+            Stmt::Continue { .. } => {
+                Err(Unwind::Continue)
+            }
+            Stmt::Export { declaration, .. } => {
+                self.execute(declaration)
             }
             Stmt::Expression { expression } => {
-                self.evaluate(expression)
+                Ok(self.evaluate(expression)?)
             }
             Stmt::Function { name, .. } => {
                 let func_name: String = name.lexeme.clone();
                 let new_fun = LiteralValue::Function(FunctionEnum::User(UserDefinedFunction {
-                    closure: Rc::new(RefCell::new(Environment::new_local(self.environment.clone()))),
+                    closure: self.environment,
                     declaration: Rc::new(RefCell::new(stmt.clone())), //This is really hacky, and might desync state
+                    is_initializer: false,
                 }));
-                self.environment.borrow_mut().define(func_name, new_fun);
+                self.heap.env_mut(self.environment).define(func_name, new_fun);
                 Ok(LiteralValue::None)
             }
             Stmt::If { condition, then_branch, else_branch } => {
@@ -463,51 +720,120 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
                     }
                 }
             }
+            Stmt::Import { path, alias, .. } => {
+                let module = self.import_module(path)?;
+                match alias {
+                    Some(alias) => {
+                        self.heap.env_mut(self.environment).define(alias.lexeme.clone(), LiteralValue::Instance(module));
+                    }
+                    None => {
+                        let exports: Vec<(String, LiteralValue)> = self.heap.fields(module.fields).iter()
+                            .map(|(name, value)| (name.clone(), value.clone()))
+                            .collect();
+                        for (name, value) in exports {
+                            self.heap.env_mut(self.environment).define(name, value);
+                        }
+                    }
+                }
+                Ok(LiteralValue::None)
+            }
             Stmt::Print { expression } => {
                 let value: LiteralValue = self.evaluate(expression)?;
-                println!("{}", value);
+                println!("{}", self.stringify(&value));
                 match io::stdout().flush() {
                     Ok(_) => {}
                     Err(_) => {
-                        return Err(InterpreterError {
-                            msg: "Error Flushing StdOut in Print Statement".to_string(),
-                            returning: false,
-                            value: None,
-                        })
+                        return Err(InterpreterError::new("Error Flushing StdOut in Print Statement".to_string()).into())
                     }
                 };
                 return Ok(LiteralValue::None);
             }
             Stmt::Return { keyword, value } => {
                 let new_value = match value {
-                    None => { None }
-                    Some(val) => { Some(self.evaluate(val)?) }
+                    None => { LiteralValue::None }
+                    Some(val) => { self.evaluate(val)? }
                 };
 
-                return Err(
-                    InterpreterError {
-                        msg: "".to_string(),
-                        returning: true,
-                        value: new_value,
+                return Err(Unwind::Return(new_value));
+            }
+            Stmt::Throw { value, .. } => {
+                let value = self.evaluate(value)?;
+                let msg = self.thrown_message(&value);
+                Err(InterpreterError::thrown(msg, value).into())
+            }
+            Stmt::Try { body, catch, finally, .. } => {
+                let roots_base = self.temp_roots.len();
+                let env_depth = self.env_stack.len();
+                let call_depth = self.call_lines.len();
+
+                let body_env = self.heap.alloc_env(Environment::new_local(self.environment));
+                let mut result = self.execute_block(body, body_env);
+
+                if let Some((name, catch_body)) = catch {
+                    result = match result {
+                        Err(Unwind::Error(err)) if err.is_catchable() => {
+                            // Anything the failed code left behind is abandoned with it
+                            self.temp_roots.truncate(roots_base);
+                            self.env_stack.truncate(env_depth);
+                            self.call_lines.truncate(call_depth);
+
+                            let mut catch_env = Environment::new_local(self.environment);
+                            catch_env.define(name.lexeme.clone(), self.error_value(err));
+                            let catch_env = self.heap.alloc_env(catch_env);
+                            self.execute_block(catch_body, catch_env)
+                        }
+                        other => { other }
+                    };
+                }
+
+                if let Some(finally) = finally {
+                    // A pending return value or thrown value must survive collections in the finally block
+                    let pending = match &result {
+                        Ok(_) | Err(Unwind::Break) | Err(Unwind::Continue) => { None }
+                        Err(Unwind::Return(value)) => { Some(value.clone()) }
+                        Err(Unwind::Error(err)) => {
+                            match &err.raised {
+                                Some(Raised::Thrown(value)) => { Some(value.clone()) }
+                                _ => { None }
+                            }
+                        }
+                    };
+                    if let Some(value) = &pending {
+                        self.temp_roots.push(value.clone());
                     }
-                );
+                    let finally_env = self.heap.alloc_env(Environment::new_local(self.environment));
+                    let finally_result = self.execute_block(finally, finally_env);
+                    if pending.is_some() {
+                        self.temp_roots.pop();
+                    }
+                    // Leaving the finally block early replaces whatever was pending
+                    finally_result?;
+                }
+                result
             }
             Stmt::Variable { name, initializer } => {
                 match initializer {
                     None => {
-                        self.environment.borrow_mut().define(name.lexeme.clone(), LiteralValue::None);
+                        self.heap.env_mut(self.environment).define(name.lexeme.clone(), LiteralValue::None);
                         Ok(LiteralValue::None)
                     }
                     Some(expr) => {
                         let value = self.evaluate(expr)?;
-                        self.environment.borrow_mut().define(name.lexeme.clone(), value);
+                        self.heap.env_mut(self.environment).define(name.lexeme.clone(), value);
                         Ok(LiteralValue::None)
                     }
                 }
             }
-            Stmt::While { condition, body } => {
+            Stmt::While { condition, body, increment } => {
                 while Interpreter::is_truthy(&self.evaluate(condition)?)? {
-                    let _ = self.execute(body)?;
+                    match self.execute(body) {
+                        Ok(_) | Err(Unwind::Continue) => {}
+                        Err(Unwind::Break) => { break; }
+                        Err(other) => { return Err(other); }
+                    }
+                    if let Some(increment) = increment {
+                        self.evaluate(increment)?;
+                    }
                 }
                 return Ok(LiteralValue::None);
             }
@@ -517,69 +843,408 @@ impl Visitor<Result<LiteralValue, InterpreterError>> for Interpreter {
 
 impl Interpreter {
     pub fn new() -> Interpreter {
-        let globals = Rc::new(RefCell::new(Environment::new_global()));
-
-        globals.borrow_mut().define("clock".to_string(),
-                                    LiteralValue::Function(FunctionEnum::Native(Clock)));
-
-        let environment = globals.clone();
+        let mut heap = Heap::new(GcConfig::from_env());
+        let mut natives = NativeRegistry::new();
+        Interpreter::register_builtins(&mut natives);
+        let globals = Interpreter::new_globals(&mut heap, &natives);
+        let environment = globals;
         Interpreter {
             environment,
             globals,
+            heap,
+            env_stack: Vec::new(),
+            temp_roots: Vec::new(),
+            call_lines: Vec::new(),
+            error_class: Rc::new(LoxClass::new("Error".to_string(), None, HashMap::new())),
+            modules: ModuleLoader::new(),
+            module_class: Rc::new(LoxClass::new("module".to_string(), None, HashMap::new())),
+            sandboxed: false,
+            script_args: Vec::new(),
+            host_roots: Rc::new(RefCell::new(HostRoots::default())),
+            natives,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Add the natives that need the tree-walker's heap or its state
+    fn register_builtins(registry: &mut NativeRegistry) {
+        lox_list::register(registry);
+        lox_map::register(registry);
+        lox_io::register(registry);
+        lox_json::register(registry);
+        registry.register_builtin("gc", Arity::Fixed(0), |interpreter, _| {
+            Ok(LiteralValue::NumValue(interpreter.collect_garbage() as f64))
+        });
+        registry.register_builtin("heap_objects", Arity::Fixed(0), |interpreter, _| {
+            Ok(LiteralValue::NumValue(interpreter.heap.stats().live_objects as f64))
+        });
+        registry.register_builtin("gc_collections", Arity::Fixed(0), |interpreter, _| {
+            Ok(LiteralValue::NumValue(interpreter.heap.stats().collections as f64))
+        });
+        registry.register_builtin("args", Arity::Fixed(0), |interpreter, _| {
+            let args = interpreter.script_args().iter().map(|arg| LiteralValue::StringValue(arg.clone())).collect();
+            Ok(LiteralValue::List(interpreter.heap.alloc_list(args)))
+        });
+    }
+
+    /// A global environment holding only the natives, the main program and
+    /// every imported module each get one
+    fn new_globals(heap: &mut Heap, registry: &NativeRegistry) -> GcRef {
+        let mut global_env = Environment::new_global();
+
+        for (id, name) in registry.entries() {
+            global_env.define(name.to_string(), LiteralValue::Function(FunctionEnum::Native(id)));
+        }
+        for (name, value) in lox_math::CONSTANTS {
+            global_env.define(name.to_string(), LiteralValue::NumValue(value));
+        }
+        heap.alloc_env(global_env)
+    }
+
+    /// Imports in the main program are resolved relative to this file
+    pub fn set_script_path(&mut self, path: &Path) {
+        self.modules.set_main_file(path);
+    }
+
+    /// Limit the steps, call depth and time each run may use
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget.set_limits(limits);
+    }
+
+    /// How big the stack of the thread running scripts is, 2 MiB unless set.
+    /// Calls nested deeper than it can hold fail with a call depth limit error.
+    pub fn set_stack_size(&mut self, bytes: usize) {
+        self.budget.set_stack_size(bytes);
+    }
+
+    /// A handle that stops the running script when triggered, from any thread
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.budget.interrupt_handle()
+    }
+
+    /// Start a new run's budget, for calls made by a host instead of `interpret`
+    pub(crate) fn start_budget(&mut self) {
+        self.budget.start();
+    }
+
+    /// Turn off the file natives and `import`, reading stdin still works
+    pub fn set_sandboxed(&mut self, sandboxed: bool) {
+        self.sandboxed = sandboxed;
+    }
+
+    pub fn sandboxed(&self) -> bool {
+        self.sandboxed
+    }
+
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
+
+    pub fn script_args(&self) -> &[String] {
+        &self.script_args
+    }
+
+    /// Text of an imported file for error reports, None for the main program
+    pub fn module_source(&self, source_id: u32) -> Option<&SourceFile> {
+        self.modules.source(source_id)
+    }
+
+    /// Globals of the module the running code was written in
+    fn current_globals(&self) -> GcRef {
+        Environment::root(&self.heap, self.environment)
+    }
+
+    /// Load a module, or reuse it if it was imported before
+    fn import_module(&mut self, path: &Token) -> Result<LoxInstance, InterpreterError> {
+        let requested = match &path.literal {
+            LiteralValue::StringValue(requested) => { requested.clone() }
+            _ => { path.lexeme.clone() }
+        };
+        if self.sandboxed {
+            return Err(lox_io::sandbox_error(&format!("import \"{requested}\"")));
+        }
+        let file = self.modules.resolve(&requested)?;
+        if let Some(module) = self.modules.cached(&file) {
+            return Ok(module);
+        }
+        self.modules.begin(&file)?;
+        let module = self.load_module(&file);
+        self.modules.finish(&file, module.as_ref().ok().cloned());
+        module
+    }
+
+    /// Run a module in its own globals and collect its exports into a module value
+    fn load_module(&mut self, file: &Path) -> Result<LoxInstance, InterpreterError> {
+        let text = fs::read_to_string(file).map_err(|err| {
+            InterpreterError::new(format!("Couldn't read module {}: {err}", file.display()))
+        })?;
+        let source_id = self.modules.add_source(file, text.clone());
+
+        let mut lexer = Lexer::for_module(text, source_id);
+        lexer.scan_tokens();
+        if let Some(error) = lexer.errors.first() {
+            return Err(lox_module::static_error(&error.message, error.span));
+        }
+        let mut statements = Parser::new(Vec::from(lexer.tokens)).parse().map_err(|errors| {
+            let error = &errors[0];
+            lox_module::static_error(&error.message, error.token.span)
+        })?;
+        Resolver::new().resolve(&mut statements).map_err(|errors| {
+            let error = &errors[0];
+            lox_module::static_error(&error.message, error.token.span)
+        })?;
+
+        let module_env = Interpreter::new_globals(&mut self.heap, &self.natives);
+        let previous: GcRef = self.environment;
+        self.env_stack.push(previous);
+        self.environment = module_env;
+        let mut result = Ok(());
+        for statement in statements.iter_mut() {
+            // The resolver keeps returns and loop jumps out of top level code
+            if let Err(Unwind::Error(err)) = self.execute(statement) {
+                result = Err(err);
+                break;
+            }
+        }
+        self.env_stack.pop();
+        self.environment = previous;
+        result?;
+
+        let module = LoxInstance::new(self.module_class.clone(), &mut self.heap);
+        for name in lox_module::exported_names(&statements) {
+            let value = self.heap.env(module_env).values.get(&name).cloned().unwrap_or(LiteralValue::None);
+            self.heap.fields_mut(module.fields).insert(name, value);
+        }
+        Ok(module)
+    }
+
+    /// What an uncaught `throw` reports, instances with a string `message`
+    /// field such as caught errors report that message
+    fn thrown_message(&self, value: &LiteralValue) -> String {
+        if let LiteralValue::Instance(instance) = value {
+            if let Some(LiteralValue::StringValue(message)) = self.heap.fields(instance.fields).get("message") {
+                return message.clone();
+            }
+        }
+        format!("Uncaught exception: {}", self.stringify(value))
+    }
+
+    /// The value a `catch` clause binds, a thrown value is passed on as is
+    /// while runtime errors become an `Error` instance with `message`, `line`
+    /// and `stack` fields
+    fn error_value(&mut self, err: InterpreterError) -> LiteralValue {
+        if let Some(Raised::Thrown(value)) = err.raised {
+            return value;
+        }
+        let line = match err.span {
+            Some(span) if span.is_known() => { LiteralValue::NumValue(span.line as f64) }
+            _ => { LiteralValue::None }
+        };
+        let mut stack: Vec<LiteralValue> = Vec::new();
+        for frame in &err.stack {
+            let mut entry = LoxMap::new();
+            entry.insert(MapKey::Str("function".to_string()), LiteralValue::StringValue(frame.function.clone()));
+            entry.insert(MapKey::Str("line".to_string()), LiteralValue::NumValue(frame.line as f64));
+            stack.push(LiteralValue::Map(self.heap.alloc_map(entry)));
+        }
+        let stack = LiteralValue::List(self.heap.alloc_list(stack));
+
+        let instance = LoxInstance::new(self.error_class.clone(), &mut self.heap);
+        let fields = self.heap.fields_mut(instance.fields);
+        fields.insert("message".to_string(), LiteralValue::StringValue(err.msg));
+        fields.insert("line".to_string(), line);
+        fields.insert("stack".to_string(), stack);
+        LiteralValue::Instance(instance)
+    }
+
+    /// Call a function or class with arguments that have already been evaluated,
+    /// `line` is where the call happened for stack traces
+    pub(crate) fn call_value(&mut self, callee: LiteralValue, args: Vec<LiteralValue>, line: i32) -> Result<LiteralValue, InterpreterError> {
+        match callee {
+            LiteralValue::Function(fun) => {
+                let arity = match &fun {
+                    FunctionEnum::Native(id) => { self.natives.arity(*id) }
+                    FunctionEnum::User(_) => { Arity::Fixed(fun.arity()?) }
+                    FunctionEnum::Host(method) => { method.arity }
+                };
+                if !arity.accepts(args.len()) {
+                    return Err(InterpreterError::new("Incorrect number of arguments".to_string()));
+                }
+                self.budget.enter_call(self.call_lines.len())?;
+                self.call_lines.push(line);
+                let result = fun.call(self, args);
+                self.call_lines.pop();
+                result
+            }
+            LiteralValue::Class(class) => {
+                if args.len() as u8 != class.arity()? {
+                    return Err(InterpreterError::new("Incorrect number of arguments".to_string()));
+                }
+                self.budget.enter_call(self.call_lines.len())?;
+                self.call_lines.push(line);
+                let result = class.call(self, args);
+                self.call_lines.pop();
+                result
+            }
+            _ => Err(InterpreterError::new("Tried to call non-callable".to_string()))
+        }
+    }
+
+    /// Line of the innermost call being executed, 0 at the top level
+    pub(crate) fn call_line(&self) -> i32 {
+        self.call_lines.last().copied().unwrap_or(0)
+    }
+
+    /// Run a full collection, everything reachable from the active
+    /// environments or from values mid-evaluation is kept
+    pub fn collect_garbage(&mut self) -> usize {
+        let mut roots: Vec<GcRef> = vec![self.globals, self.environment];
+        roots.extend(self.env_stack.iter().copied());
+        roots.extend(self.modules.modules().map(|module| module.fields));
+        let values: Vec<LiteralValue> = self.temp_roots.iter().chain(self.host_roots.borrow().values()).cloned().collect();
+        self.heap.collect(&roots, &values)
+    }
+
+    /// Add a Rust native and define it in the main program's globals, modules
+    /// imported afterwards see it too
+    pub fn register_native(&mut self, name: &str, arity: Arity, function: impl Fn(&Args) -> Result<Value, engine::Error> + 'static) {
+        let id = self.natives.register(name, arity, function);
+        let native = LiteralValue::Function(FunctionEnum::Native(id));
+        self.heap.env_mut(self.globals).define(name.to_string(), native);
+    }
+
+    /// Run a native. Builtins get the interpreter's own values, natives the
+    /// host registered get them copied out to host values and the result copied back in.
+    pub(crate) fn call_native(&mut self, id: usize, arguments: Vec<LiteralValue>) -> Result<LiteralValue, InterpreterError> {
+        let function = match self.natives.body(id) {
+            NativeBody::Builtin(builtin) => { return builtin(self, &arguments); }
+            NativeBody::Host(function) => { function }
+        };
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in &arguments {
+            values.push(engine::to_host(self, argument)?);
+        }
+        let result = function(&Args::new(self.natives.name(id), &values))?;
+        Ok(engine::to_lox(self, &result)?)
+    }
+
+    /// A handle for the host that keeps `value` alive until it is dropped
+    pub(crate) fn root_for_host(&mut self, value: &LiteralValue) -> Handle {
+        HostRoots::root(&self.host_roots, value)
+    }
+
+    /// Whether a handle was handed out by this interpreter
+    pub(crate) fn owns_handle(&self, handle: &Handle) -> bool {
+        HostRoots::holds(&self.host_roots, handle)
+    }
+
+    /// Evaluate call arguments in order, rooting each one so a collection
+    /// triggered by a later argument can't free an earlier one
+    fn evaluate_arguments(&mut self, arguments: &mut Vec<Box<Expr>>) -> Result<Vec<LiteralValue>, InterpreterError> {
+        let roots_base = self.temp_roots.len();
+        let mut args: Vec<LiteralValue> = Vec::new();
+        for argument in arguments {
+            match self.evaluate(argument) {
+                Ok(value) => {
+                    self.temp_roots.push(value.clone());
+                    args.push(value);
+                }
+                Err(err) => {
+                    self.temp_roots.truncate(roots_base);
+                    return Err(err);
+                }
+            }
+        }
+        self.temp_roots.truncate(roots_base);
+        Ok(args)
+    }
+
+    fn evaluate_map_entry(&mut self, key: &mut Box<Expr>, value: &mut Box<Expr>) -> Result<(MapKey, LiteralValue), InterpreterError> {
+        let key_span = key.span();
+        let key = self.evaluate(key)?;
+        let key = MapKey::from_value(&key).map_err(|mut err| {
+            err.span = Some(key_span);
+            err
+        })?;
+        let value = self.evaluate(value)?;
+        Ok((key, value))
+    }
+
+    /// Find a variable using the scope distance computed by the resolver,
+    /// unresolved variables are looked up in the globals
+    fn look_up_variable(&self, name: &Token, depth: &Option<usize>) -> Result<LiteralValue, EnvironmentError> {
+        match depth {
+            None => { Environment::get(&self.heap, self.current_globals(), name) }
+            Some(distance) => { Environment::get_at(&self.heap, self.environment, *distance, name) }
         }
     }
 
     fn evaluate(&mut self, expr: &mut Box<Expr>) -> Result<LiteralValue, InterpreterError> {
-        return self.visit_expr(expr);
+        return self.visit_expr(expr).map_err(|mut err| {
+            if err.span.is_none() {
+                err.span = Some(expr.span());
+            }
+            err
+        });
     }
 
     pub fn interpret(&mut self, statements: &mut Vec<Stmt>) -> Result<LiteralValue, InterpreterError> {
+        self.budget.start();
         let mut last_val: LiteralValue = LiteralValue::None;
         for statement in statements.iter_mut() {
             last_val = match self.execute(statement) {
                 Ok(value) => { value }
-                Err(_) => {
-                    return Err(InterpreterError {
-                        msg: "Error Executing Statemment".to_string(),
-                        returning: false,
-                        value: None,
-                    })
-                }
+                Err(Unwind::Error(err)) => { return Err(err); }
+                // The resolver rejects top level returns, so this can't happen
+                Err(Unwind::Return(value)) => { return Ok(value); }
+                // Likewise for break and continue outside of a loop
+                Err(Unwind::Break) | Err(Unwind::Continue) => { return Ok(LiteralValue::None); }
             };
         }
         return Ok(last_val);
     }
 
-    fn execute(&mut self, stmt: &mut Stmt) -> Result<LiteralValue, InterpreterError> {
-        self.visit_stmt(stmt)
+    fn execute(&mut self, stmt: &mut Stmt) -> Result<LiteralValue, Unwind> {
+        if let Err(mut err) = self.budget.step() {
+            err.span = stmt.span();
+            return Err(Unwind::Error(err));
+        }
+        // Statement boundaries are the safe points for automatic collection
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.visit_stmt(stmt).map_err(|unwind| match unwind {
+            Unwind::Error(mut err) => {
+                if err.span.is_none() {
+                    err.span = stmt.span();
+                }
+                Unwind::Error(err)
+            }
+            other => { other }
+        })
     }
 
-    pub(crate) fn execute_block(&mut self, statements: &mut Vec<Box<Stmt>>, environment: Rc<RefCell<Environment>>) -> Result<LiteralValue, InterpreterError> {
-        let previous: Rc<RefCell<Environment>> = self.environment.clone();
+    pub(crate) fn execute_block(&mut self, statements: &mut Vec<Box<Stmt>>, environment: GcRef) -> Result<LiteralValue, Unwind> {
+        let previous: GcRef = self.environment;
+        self.env_stack.push(previous);
         self.environment = environment;
         let mut last_val: LiteralValue;
         for stmt in statements {
             last_val = match self.execute(stmt) {
                 Ok(value) => { value }
                 Err(err) => {
-                    if err.returning {
-                        self.environment = previous;
-                        return Err(err);
-                    }
+                    self.env_stack.pop();
                     self.environment = previous;
-                    return Err(InterpreterError {
-                        msg: "Error executing Block".to_string(),
-                        returning: false,
-                        value: None,
-                    });
+                    return Err(err);
                 }
             };
         }
+        self.env_stack.pop();
         self.environment = previous;
         Ok(LiteralValue::None)
     }
 
-    fn is_truthy(value: &LiteralValue) -> Result<bool, InterpreterError> {
+    pub(crate) fn is_truthy(value: &LiteralValue) -> Result<bool, InterpreterError> {
         match value {
             LiteralValue::None => { Ok(false) }
             LiteralValue::True => { Ok(true) }
@@ -599,34 +1264,102 @@ impl Interpreter {
                 }
             }
             LiteralValue::IdentifierValue(_) => {
-                Err(InterpreterError {
-                    msg: "Tried to evaluate truthiness of identifier value".to_string(),
-                    returning: false,
-                    value: None,
-                })
+                Err(InterpreterError::new("Tried to evaluate truthiness of identifier value".to_string()))
             }
             LiteralValue::Function(_) => {
-                Err(InterpreterError {
-                    msg: "Tried to evaluate truthiness of callable".to_string(),
-                    returning: false,
-                    value: None,
-                })
+                Err(InterpreterError::new("Tried to evaluate truthiness of callable".to_string()))
+            }
+            LiteralValue::Class(_) => {
+                Err(InterpreterError::new("Tried to evaluate truthiness of class".to_string()))
             }
+            LiteralValue::Instance(_) => { Ok(true) }
+            LiteralValue::List(_) => { Ok(true) }
+            LiteralValue::Map(_) => { Ok(true) }
+            LiteralValue::Host(_) => { Ok(true) }
         }
     }
 
-    fn unwrap_interpreter_result(result: Result<LiteralValue, InterpreterError>) -> Result<LiteralValue, InterpreterError> {
-        match result {
-            Ok(val) => { Ok(val) }
-            Err(error) => {
-                if error.returning {
-                    return match error.value.clone() {
-                        None => { Ok(LiteralValue::None) }
-                        Some(v) => { Ok(v) }
-                    }
+    /// Instances, classes and functions are equal only to themselves, host
+    /// objects decide for themselves what they equal. None if neither side is
+    /// one of these, so the values are compared by `partial_cmp`.
+    fn identity_equality(lhs: &LiteralValue, rhs: &LiteralValue) -> Option<bool> {
+        match (lhs, rhs) {
+            (LiteralValue::Instance(lhs), LiteralValue::Instance(rhs)) => { Some(lhs == rhs) }
+            (LiteralValue::Class(lhs), LiteralValue::Class(rhs)) => { Some(Rc::ptr_eq(lhs, rhs)) }
+            (LiteralValue::Function(lhs), LiteralValue::Function(rhs)) => { Some(Interpreter::same_function(lhs, rhs)) }
+            (LiteralValue::Host(lhs), LiteralValue::Host(rhs)) => { Some(lhs == rhs) }
+            (LiteralValue::List(lhs), LiteralValue::List(rhs)) => { Some(lhs == rhs) }
+            (LiteralValue::Map(lhs), LiteralValue::Map(rhs)) => { Some(lhs == rhs) }
+            (lhs, rhs) if Interpreter::is_object(lhs) || Interpreter::is_object(rhs) => { Some(false) }
+            _ => { None }
+        }
+    }
+
+    fn is_object(value: &LiteralValue) -> bool {
+        matches!(
+            value,
+            LiteralValue::Instance(_)
+                | LiteralValue::Class(_)
+                | LiteralValue::Function(_)
+                | LiteralValue::Host(_)
+                | LiteralValue::List(_)
+                | LiteralValue::Map(_)
+        )
+    }
+
+    /// Functions are the same if they come from the same declaration closing
+    /// over the same environment, bound methods included
+    fn same_function(lhs: &FunctionEnum, rhs: &FunctionEnum) -> bool {
+        match (lhs, rhs) {
+            (FunctionEnum::User(lhs), FunctionEnum::User(rhs)) => {
+                lhs.closure == rhs.closure && Rc::ptr_eq(&lhs.declaration, &rhs.declaration)
+            }
+            (FunctionEnum::Native(lhs), FunctionEnum::Native(rhs)) => { lhs == rhs }
+            (FunctionEnum::Host(lhs), FunctionEnum::Host(rhs)) => { Rc::ptr_eq(lhs, rhs) }
+            _ => { false }
+        }
+    }
+
+    /// Format a value the way `print` shows it, following lists and maps into the heap
+    pub fn stringify(&self, value: &LiteralValue) -> String {
+        self.stringify_nested(value, &mut Vec::new())
+    }
+
+    fn stringify_nested(&self, value: &LiteralValue, open: &mut Vec<GcRef>) -> String {
+        match value {
+            LiteralValue::List(list) => {
+                // A list that contains itself is shown as [...] instead of recursing forever
+                if open.contains(list) {
+                    return "[...]".to_string();
+                }
+                open.push(*list);
+                let elements: Vec<String> = self.heap.list(*list).iter()
+                    .map(|element| self.stringify_element(element, open))
+                    .collect();
+                open.pop();
+                format!("[{}]", elements.join(", "))
+            }
+            LiteralValue::Map(map) => {
+                if open.contains(map) {
+                    return "{...}".to_string();
                 }
-                Err(error)
+                open.push(*map);
+                let entries: Vec<String> = self.heap.map(*map).entries().iter()
+                    .map(|(key, value)| format!("{}: {}", self.stringify_element(&key.to_value(), open),
+                                                self.stringify_element(value, open)))
+                    .collect();
+                open.pop();
+                format!("{{{}}}", entries.join(", "))
             }
+            _ => { value.to_string() }
+        }
+    }
+
+    /// Strings inside a list or map are quoted so they stand apart from other values
+    fn stringify_element(&self, value: &LiteralValue, open: &mut Vec<GcRef>) -> String {
+        match value {
+            LiteralValue::StringValue(s) => { format!("\"{s}\"") }
+            _ => { self.stringify_nested(value, open) }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,33 @@
+mod scanner;
+mod lox;
+mod ast;
+mod printer;
+mod token;
+mod parser;
+mod interpreter;
+mod environment;
+mod lox_budget;
+mod lox_callable;
+mod lox_class;
+mod lox_host;
+mod lox_io;
+mod lox_json;
+mod lox_list;
+mod lox_map;
+mod lox_math;
+mod lox_module;
+mod lox_native;
+mod lox_string;
+mod resolver;
+mod chunk;
+mod compiler;
+mod vm;
+mod gc;
+mod engine;
+
+pub use engine::{Diagnostic, Engine, Error, Handle, Value};
+pub use interpreter::StackFrame;
+pub use lox::{Backend, Lox};
+pub use lox_budget::{InterruptHandle, LimitExceeded, Limits};
+pub use lox_host::{HostObject, HostRef};
+pub use lox_native::{Args, Arity, FromValue};
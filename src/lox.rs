@@ -5,46 +5,178 @@ use std::path::Path;
 use std::process;
 
 use crate::{scanner, token};
-use crate::ast::{Expr, Stmt};
-use crate::parser::Parser;
-use crate::token::{LiteralValue, TokenType};
-use crate::interpreter::{Interpreter};
+use crate::ast::Stmt;
+use crate::parser::{ParseError, Parser};
+use crate::token::{Span, TokenType};
+use crate::interpreter::{self, Interpreter, InterpreterError, StackFrame};
+use crate::lox_budget::{InterruptHandle, Limits};
+use crate::resolver::Resolver;
+use crate::compiler::Compiler;
+use crate::vm::{Value, Vm, VmError};
 
+/// Which runtime executes the parsed program
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backend {
+    TreeWalker,
+    Bytecode,
+}
+
+/// A session that scripts run in. Globals, functions and classes defined by
+/// one `run` stay defined for the next, the REPL runs each line this way.
 pub struct Lox {
     had_error: bool,
+    // Whether the error that stopped the program happened while running it
+    had_runtime_error: bool,
+    // Status the script asked for by calling `exit()`
+    exit_code: Option<u8>,
+    backend: Backend,
+    // Text that error spans point into, everything run so far in the session
+    source: String,
+    // Only the one matching `backend` is used, it lives as long as the session
+    interpreter: Interpreter,
+    vm: Vm,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Lox::new()
+    }
 }
 
 impl Lox {
     pub fn new() -> Lox {
+        Lox::with_backend(Backend::TreeWalker)
+    }
+
+    pub fn with_backend(backend: Backend) -> Lox {
+        let interpreter = Interpreter::new();
+        let mut vm = Vm::new();
+        vm.set_interrupt_handle(interpreter.interrupt_handle());
         Lox {
-            had_error: false
+            had_error: false,
+            had_runtime_error: false,
+            exit_code: None,
+            backend,
+            source: String::new(),
+            interpreter,
+            vm,
         }
     }
+
+    /// Disable file access for the scripts this session runs
+    pub fn set_sandboxed(&mut self, sandboxed: bool) {
+        self.interpreter.set_sandboxed(sandboxed);
+    }
+
+    /// Limit the steps, call depth and time each run may use
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.interpreter.set_limits(limits);
+        self.vm.set_limits(limits);
+    }
+
+    /// How big the stack of the thread running the session is, see `Interpreter::set_stack_size`
+    pub fn set_stack_size(&mut self, bytes: usize) {
+        self.interpreter.set_stack_size(bytes);
+        self.vm.set_stack_size(bytes);
+    }
+
+    /// A handle another thread can use to stop the script that is running
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interpreter.interrupt_handle()
+    }
+
+    /// Arguments after the script path, handed to the program through `args()`.
+    /// The VM has no lists to hand them over in, so it rejects `args()` when compiling.
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.interpreter.set_script_args(args);
+    }
+
+    /// Run a program in the session, on top of whatever earlier runs defined
     pub fn run(&mut self, program: String) {
-        let mut lexer = scanner::Lexer::new(program);
+        self.run_source(program);
+    }
+
+    /// Scan, parse, resolve and run a piece of source, returning how the value
+    /// of its last statement prints if it ran without errors
+    fn run_source(&mut self, program: String) -> Option<String> {
+        self.had_error = false;
+        self.had_runtime_error = false;
+
+        // Spans keep counting from the end of the previous run, so errors in
+        // functions it defined still point at the right text
+        if !self.source.is_empty() && !self.source.ends_with('\n') {
+            self.source.push('\n');
+        }
+        let mut lexer = scanner::Lexer::continuing(program.clone(), self.source.len(), self.source.lines().count() as i32 + 1);
+        self.source.push_str(&program);
         lexer.scan_tokens();
+        self.report_scan_errors(&lexer.errors);
 
         let mut parser = Parser::new(Vec::from(lexer.tokens));
         let mut statements = match parser.parse() {
             Ok(stmts) => stmts,
-            Err(_) => {
-                self.had_error = true;
-                vec![Stmt::new_expression(Expr::new_literal(LiteralValue::None))]
+            Err(errors) => {
+                self.report_parse_errors(errors);
+                return None;
             }
         };
 
-        let mut interpreter = Interpreter::new();
+        // Don't report resolver errors for code that didn't even scan cleanly
+        if self.had_error { return None; }
 
-        let result = interpreter.interpret(&mut statements);
-        match result {
-            Ok(_) => {}
+        let mut resolver = Resolver::new();
+        if let Err(errors) = resolver.resolve(&mut statements) {
+            for error in errors {
+                self.error_token(&error.token, &error.message);
+            }
+        }
+
+        // Don't run code that failed static checks
+        if self.had_error { return None; }
+
+        if self.backend == Backend::Bytecode {
+            return self.run_bytecode(&statements).map(|value| value.to_string());
+        }
+
+        match self.interpreter.interpret(&mut statements) {
+            Ok(value) => { Some(self.interpreter.stringify(&value)) }
             Err(err) => {
-                self.had_error = true;
-                println!("{}", err.msg)
+                match err.exit_code() {
+                    Some(code) => { self.exit_code = Some(code); }
+                    None => { self.report_runtime_error(&err); }
+                }
+                None
             }
         }
     }
 
+    /// Compile and run statements on the virtual machine, returning the value
+    /// of the last statement if nothing went wrong
+    fn run_bytecode(&mut self, statements: &Vec<Stmt>) -> Option<Value> {
+        let mut compiler = Compiler::new();
+        compiler.define_globals(self.vm.global_names());
+        let function = match compiler.compile(statements) {
+            Ok(function) => { function }
+            Err(err) => {
+                self.error_token(&err.token, &err.message);
+                return None;
+            }
+        };
+
+        match self.vm.interpret(function) {
+            Ok(value) => { Some(value) }
+            Err(VmError { exit_code: Some(code), .. }) => {
+                self.exit_code = Some(code);
+                None
+            }
+            Err(err) => {
+                self.had_runtime_error = true;
+                self.report(err.span, "", &err.msg);
+                Lox::report_stack(&err.stack);
+                None
+            }
+        }
+    }
 
     pub fn run_file(&mut self, program_path: &str) {
         let file_path = Path::new(program_path);
@@ -62,15 +194,16 @@ impl Lox {
             Ok(_) => {}
         };
 
+        self.interpreter.set_script_path(file_path);
         self.run(contents);
 
-        // Indicate an error in th exit code.
+        // Indicate an error in the exit code, 65 for static errors and 70 for runtime ones
+        if let Some(code) = self.exit_code { process::exit(code as i32) }
+        if self.had_runtime_error { process::exit(70) }
         if self.had_error { process::exit(65) }
     }
 
     pub fn run_prompt(&mut self) {
-        // Create Interpreter 
-        let mut interpreter = Interpreter::new();
         loop {
             // Start of input
             print!(">");
@@ -88,46 +221,101 @@ impl Lox {
                 Ok(_) => {}
             };
 
-            // Scan the input
-            let mut lexer = scanner::Lexer::new(input);
-            lexer.scan_tokens();
-
-            // Parse the input
-            let mut parser = Parser::new(Vec::from(lexer.tokens));
-            let mut statements = match parser.parse() {
-                Ok(stmts) => stmts,
-                Err(_) => {
-                    self.had_error = true;
-                    vec![Stmt::new_expression(Expr::new_literal(LiteralValue::None))]
-                }
-            };
+            // Echo the value of the line, errors have already been reported
+            if let Some(value) = self.run_source(input) {
+                println!("{value}");
+                stdout().flush().unwrap();
+            }
+            if let Some(code) = self.exit_code {
+                process::exit(code as i32);
+            }
+        }
+    }
 
-            // Interpret the result
-            match interpreter.interpret(&mut statements) {
-                Ok(value) => {
-                    println!("{value}");
-                    stdout().flush().unwrap();
-                }
-                Err(err) => { println!("{}", err.msg); }
-            };
+    /// Print every syntax error found in the program
+    fn report_parse_errors(&mut self, errors: Vec<ParseError>) {
+        for error in errors {
+            self.error_token(&error.token, &error.message);
+        }
+    }
+
+    fn report_scan_errors(&mut self, errors: &Vec<scanner::ScanError>) {
+        for error in errors {
+            self.report(error.span, "", &error.message);
+        }
+    }
 
-            self.had_error = false;
+    /// Print an error from the tree-walker, which may point into an imported
+    /// file instead of the main program
+    fn report_runtime_error(&mut self, err: &InterpreterError) {
+        // An imported file that doesn't compile fails the program like a static error in it would
+        if err.is_static() {
+            self.had_error = true;
+        } else {
+            self.had_runtime_error = true;
+        }
+        let span = err.span.unwrap_or_default();
+        match self.interpreter.module_source(span.source) {
+            None => { self.report(span, "", &err.msg); }
+            Some(file) => { Lox::print_report(&file.text, Some(&file.name), span, "", &err.msg); }
         }
+        Lox::report_stack(&err.stack);
     }
 
-    pub fn error(line: i32, message: &str) {
-        Lox::report(line, "", message)
+    /// Print the calls a runtime error escaped from, innermost first
+    fn report_stack(stack: &[StackFrame]) {
+        for (frame, repeats) in interpreter::collapse_frames(stack) {
+            println!("  in {}() called from line {}", frame.function, frame.line);
+            if repeats > 0 {
+                println!("  ... repeated {repeats} more times");
+            }
+        }
     }
 
-    pub fn error_token(token: token::Token, message: &str) {
+    pub fn error_token(&mut self, token: &token::Token, message: &str) {
         if token.token_type == TokenType::Eof {
-            Lox::report(token.line, "at end", message)
+            self.report(token.span, " at end", message)
         } else {
-            Lox::report(token.line, &format!(" at '{}'", token.lexeme), message)
+            self.report(token.span, &format!(" at '{}'", token.lexeme), message)
         }
     }
 
-    fn report(line: i32, report_where: &str, message: &str) {
-        println!("[ {0}] Error {1}: {2}", line, report_where, message)
+    /// Print an error followed by the source line it happened on, with the
+    /// offending span underlined
+    fn report(&mut self, span: Span, report_where: &str, message: &str) {
+        self.had_error = true;
+        Lox::print_report(&self.source, None, span, report_where, message);
+    }
+
+    /// `file` names the imported file `source` was read from, it is left out
+    /// for the main program
+    fn print_report(source: &str, file: Option<&str>, span: Span, report_where: &str, message: &str) {
+        let file = match file {
+            None => { String::new() }
+            Some(name) => { format!(" in {name}") }
+        };
+        if !span.is_known() {
+            println!("[line {0}{1}] Error{2}: {3}", span.line, file, report_where, message);
+            return;
+        }
+        println!("[line {0}:{1}{2}] Error{3}: {4}", span.line, span.column, file, report_where, message);
+
+        let line_text = match source.lines().nth((span.line - 1) as usize) {
+            None => { return; }
+            Some(text) => { text }
+        };
+        // Spans covering several lines are only underlined up to the end of the first
+        let end = (span.offset + span.length).min(source.len());
+        let width = match source.get(span.offset..end) {
+            None => { 1 }
+            Some(text) => { text.chars().take_while(|c| *c != '\n').count().max(1) }
+        };
+        // Keep tabs so the carets line up with the excerpt
+        let indent: String = line_text.chars().take(span.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(span.line.to_string().len());
+        println!("{} | {}", span.line, line_text);
+        println!("{} | {}{}", gutter, indent, "^".repeat(width));
     }
 }
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::hint;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::interpreter::InterpreterError;
+
+// Reading the clock on every statement would slow tight loops down noticeably
+const CLOCK_CHECK_INTERVAL: u64 = 256;
+
+// What Rust gives the threads it spawns, assumed unless the host says otherwise
+const DEFAULT_STACK_SIZE: usize = 2 << 20;
+
+// Stack left free below the deepest call, for the frames of the call itself and
+// whatever the code it runs needs before the next call is checked
+const STACK_RESERVE: usize = 1 << 20;
+
+/// How much work one run may do before it is stopped, nothing is limited by default.
+/// Steps are executed statements, loop iterations included, or executed
+/// instructions on the bytecode VM. Calls nested deeper than the stack can
+/// hold always fail as `CallDepth`, see `Engine::set_stack_size`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub max_call_depth: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+/// Which limit stopped a script
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitExceeded {
+    Steps,
+    CallDepth,
+    Timeout,
+    /// `InterruptHandle::interrupt` was called
+    Interrupted,
+}
+
+/// Cancels whatever the interpreter it came from is running, from any thread.
+/// It counts the interrupts sent, so one sent while nothing runs isn't lost.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicU64>);
+
+/// The limits and how much of them the current run has used up
+pub struct Budget {
+    limits: Limits,
+    steps: u64,
+    started: Instant,
+    interrupt: InterruptHandle,
+    // How many interrupts had been sent when one last stopped a run
+    interrupts_seen: u64,
+    // Whether this run was interrupted, it stays stopped until the next one starts
+    interrupted: bool,
+    stack_size: usize,
+    // Where the stack was when the run started, calls are measured from here
+    stack_start: usize,
+}
+
+impl InterruptHandle {
+    /// Stop the running script at its next statement, or the next script to
+    /// run if none is running. Scripts can't catch this.
+    pub fn interrupt(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Budget {
+    pub fn new() -> Budget {
+        Budget {
+            limits: Limits::default(),
+            steps: 0,
+            started: Instant::now(),
+            interrupt: InterruptHandle::default(),
+            interrupts_seen: 0,
+            interrupted: false,
+            stack_size: DEFAULT_STACK_SIZE,
+            stack_start: stack_position(),
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Be stopped through `handle` instead of this budget's own, so one handle
+    /// reaches whichever backend a session runs on
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.interrupt = handle;
+    }
+
+    /// How big the stack of the thread running scripts is, calls stop
+    /// nesting well before they would overflow it
+    pub fn set_stack_size(&mut self, bytes: usize) {
+        self.stack_size = bytes;
+    }
+
+    /// Start counting steps and time for a new run. Interrupts that already
+    /// stopped an earlier run don't carry over, ones nothing has seen yet do.
+    pub fn start(&mut self) {
+        self.steps = 0;
+        self.started = Instant::now();
+        self.interrupted = false;
+        self.stack_start = stack_position();
+    }
+
+    /// Account for one statement. Once a limit is hit every later step fails
+    /// too, so `finally` blocks can't keep the script going.
+    pub fn step(&mut self) -> Result<(), InterpreterError> {
+        self.steps += 1;
+        let interrupts = self.interrupt.0.load(Ordering::Relaxed);
+        if self.interrupted || interrupts != self.interrupts_seen {
+            self.interrupts_seen = interrupts;
+            self.interrupted = true;
+            return Err(InterpreterError::limit(LimitExceeded::Interrupted, "Script was interrupted.".to_string()));
+        }
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(InterpreterError::limit(LimitExceeded::Steps, format!("Step limit of {max_steps} exceeded.")));
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.steps.is_multiple_of(CLOCK_CHECK_INTERVAL) && self.started.elapsed() > timeout {
+                return Err(InterpreterError::limit(LimitExceeded::Timeout, format!("Time limit of {timeout:?} exceeded.")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a call can be made while `depth` calls are already in progress
+    pub fn enter_call(&self, depth: usize) -> Result<(), InterpreterError> {
+        if let Some(max_call_depth) = self.limits.max_call_depth {
+            if depth >= max_call_depth {
+                return Err(InterpreterError::limit(LimitExceeded::CallDepth, format!("Call depth limit of {max_call_depth} exceeded.")));
+            }
+        }
+        // Each call recurses through the interpreter, so deep enough recursion would crash the process
+        let used = self.stack_start.abs_diff(stack_position());
+        if used > self.stack_size.saturating_sub(STACK_RESERVE) {
+            return Err(InterpreterError::limit(LimitExceeded::CallDepth, "Stack overflow, calls are nested too deeply.".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Address of a local in this function's frame, which moves as the stack grows
+#[inline(never)]
+fn stack_position() -> usize {
+    let marker = 0u8;
+    hint::black_box(&marker) as *const u8 as usize
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget::new()
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::Steps => write!(f, "step limit exceeded"),
+            LimitExceeded::CallDepth => write!(f, "call depth limit exceeded"),
+            LimitExceeded::Timeout => write!(f, "time limit exceeded"),
+            LimitExceeded::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
@@ -3,10 +3,12 @@ use std::fmt::Debug;
 use std::ops::Deref;
 use std::rc::Rc;
 use crate::ast::Stmt;
-use crate::interpreter::{Interpreter, InterpreterError};
-use crate::token::{FunctionEnum, LiteralValue, NativeFunctions, UserDefinedFunction};
-use std::time::SystemTime;
+use crate::interpreter::{Interpreter, InterpreterError, StackFrame, Unwind};
+use crate::token::{FunctionEnum, LiteralValue, TokenType, UserDefinedFunction};
 use crate::environment::Environment;
+use crate::gc::Heap;
+use crate::lox_class::{LoxClass, LoxInstance};
+use crate::token::Token;
 
 pub trait LoxCallable: Debug + PartialEq {
     fn call(&self, interpreter: &mut Interpreter,
@@ -15,53 +17,40 @@ pub trait LoxCallable: Debug + PartialEq {
     fn arity(&self) -> Result<u8, InterpreterError>;
 }
 
-impl LoxCallable for NativeFunctions {
-    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, InterpreterError> {
-        match self {
-            NativeFunctions::Clock => {
-                let now = SystemTime::now();
-                let now_in_ms = now.duration_since(SystemTime::UNIX_EPOCH).expect("Error Getting Time").as_millis();
-                Ok(LiteralValue::NumValue(now_in_ms as f64))
-            }
-        }
-    }
-
-    fn arity(&self) -> Result<u8, InterpreterError> {
-        match self {
-            NativeFunctions::Clock => {Ok(0u8)}
-        }
-    }
-}
-
 impl LoxCallable for UserDefinedFunction {
     fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, InterpreterError> {
         let (mut name, mut params, mut body) = match self.declaration.borrow().deref() {
             Stmt::Function {name,  params, body } => {(name.clone(), 
                 params.clone(), body.clone())}
-            _ => {return Err(InterpreterError{msg: "Invalid function declaration".to_string(),
-                returning: false,
-                value: None})}
+            _ => {return Err(InterpreterError::new("Invalid function declaration".to_string()))}
         };
 
-        let call_env = Rc::new(RefCell::new(Environment::new_local(self.closure.clone())));
+        let mut call_env = Environment::new_local(self.closure);
         
         for i in 0..params.len() {
-            call_env.clone().borrow_mut().define(params[i].lexeme.clone(), arguments[i].clone());
+            call_env.define(params[i].lexeme.clone(), arguments[i].clone());
         }
+        let call_env = interpreter.heap.alloc_env(call_env);
         
-        match interpreter.execute_block(&mut body, call_env.clone()){
-            Ok(val) => {Ok(val)}
-            Err(err) => {
-                if err.returning {
-                    match err.value {
-                        None => {Ok(LiteralValue::None)}
-                        Some(val) => {Ok(val)}
-                    }
-                } else {
-                    Ok(LiteralValue::None)
-                }
+        let result = match interpreter.execute_block(&mut body, call_env){
+            Ok(_) => {LiteralValue::None}
+            Err(Unwind::Return(val)) => {val}
+            // The resolver keeps loop jumps from crossing a function boundary
+            Err(Unwind::Break) | Err(Unwind::Continue) => {LiteralValue::None}
+            Err(Unwind::Error(mut err)) => {
+                err.stack.push(StackFrame {
+                    function: name.lexeme.clone(),
+                    line: interpreter.call_line(),
+                });
+                return Err(err);
             }
-        }        
+        };
+
+        // Initializers always hand back the instance being constructed
+        if self.is_initializer {
+            return self.get_this(&name, &interpreter.heap);
+        }
+        Ok(result)
     }
 
     fn arity(&self) -> Result<u8, InterpreterError> {
@@ -70,37 +59,79 @@ impl LoxCallable for UserDefinedFunction {
                 Ok(params.len() as u8)
             }
             _ => {
-                Err(InterpreterError {
-                    msg: "Invalid Function Definition".to_string(), 
-                    returning: false, 
-                    value: None,
-                })
+                Err(InterpreterError::new("Invalid Function Definition".to_string()))
             }
         }
     }
 }
 
+impl UserDefinedFunction {
+    /// Create a copy of this method whose closure has `this` bound to the instance
+    pub fn bind(&self, instance: LoxInstance, heap: &mut Heap) -> UserDefinedFunction {
+        let mut environment = Environment::new_local(self.closure);
+        environment.define("this".to_string(), LiteralValue::Instance(instance));
+        UserDefinedFunction {
+            closure: heap.alloc_env(environment),
+            declaration: self.declaration.clone(),
+            is_initializer: self.is_initializer,
+        }
+    }
+
+    fn get_this(&self, name: &Token, heap: &Heap) -> Result<LiteralValue, InterpreterError> {
+        let this_token = Token::spanning(TokenType::This, "this".to_string(), LiteralValue::None, name.span);
+        match Environment::get_at(heap, self.closure, 0, &this_token) {
+            Ok(val) => {Ok(val)}
+            Err(err) => {
+                Err(InterpreterError::new(err.msg))
+            }
+        }
+    }
+}
+
+impl LoxCallable for Rc<LoxClass> {
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, InterpreterError> {
+        let instance = LoxInstance::new(self.clone(), &mut interpreter.heap);
+        if let Some(initializer) = self.find_method("init") {
+            initializer.bind(instance.clone(), &mut interpreter.heap).call(interpreter, arguments)?;
+        }
+        Ok(LiteralValue::Instance(instance))
+    }
+
+    fn arity(&self) -> Result<u8, InterpreterError> {
+        match self.find_method("init") {
+            None => {Ok(0u8)}
+            Some(initializer) => {initializer.arity()}
+        }
+    }
+}
 
 impl LoxCallable for FunctionEnum {
     fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, InterpreterError> {
         match self {
-            FunctionEnum::Native(fun) => {
-                fun.call(interpreter, arguments)
+            FunctionEnum::Native(id) => {
+                interpreter.call_native(*id, arguments)
             }
             FunctionEnum::User(fun) => {
                 fun.call(interpreter, arguments)
             }
+            FunctionEnum::Host(method) => {
+                method.call(interpreter, arguments)
+            }
         }
     }
 
     fn arity(&self) -> Result<u8, InterpreterError> {
         match self {
-            FunctionEnum::Native(fun) => {
-                fun.arity()
+            // The registry checks natives against their own `Arity`
+            FunctionEnum::Native(_) => {
+                Ok(0u8)
             }
             FunctionEnum::User(fun) => {
                 fun.arity()
             }
+            FunctionEnum::Host(method) => {
+                method.arity()
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::gc::{GcRef, Heap};
+use crate::interpreter::InterpreterError;
+use crate::token::{FunctionEnum, LiteralValue, Token, UserDefinedFunction};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<String, UserDefinedFunction>,
+}
+
+/// An instance of a Lox class, fields live on the garbage collected heap and
+/// are shared between all copies of the value so that two variables holding
+/// the same instance see the same state
+#[derive(Clone)]
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    pub fields: GcRef,
+}
+
+impl LoxClass {
+    pub fn new(name: String, superclass: Option<Rc<LoxClass>>,
+               methods: HashMap<String, UserDefinedFunction>) -> LoxClass {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    /// Look up a method on this class, falling back to the superclass chain
+    pub fn find_method(&self, name: &str) -> Option<UserDefinedFunction> {
+        match self.methods.get(name) {
+            Some(method) => { Some(method.clone()) }
+            None => {
+                match &self.superclass {
+                    None => { None }
+                    Some(superclass) => { superclass.find_method(name) }
+                }
+            }
+        }
+    }
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>, heap: &mut Heap) -> LoxInstance {
+        LoxInstance {
+            class,
+            fields: heap.alloc_fields(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, name: &Token, heap: &mut Heap) -> Result<LiteralValue, InterpreterError> {
+        // Fields shadow methods
+        if let Some(value) = heap.fields(self.fields).get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        match self.class.find_method(&name.lexeme) {
+            Some(method) => {
+                Ok(LiteralValue::Function(FunctionEnum::User(method.bind(self.clone(), heap))))
+            }
+            None => {
+                Err(InterpreterError::new(format!("Undefined property '{}'.", name.lexeme)))
+            }
+        }
+    }
+
+    pub fn set(&self, name: &Token, value: LiteralValue, heap: &mut Heap) {
+        heap.fields_mut(self.fields).insert(name.lexeme.clone(), value);
+    }
+}
+
+// Instances are compared by identity, not by the contents of their fields
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields == other.fields
+    }
+}
+
+// Fields can refer back to the instance, so only the class name is printed
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LoxInstance {{ class: {} }}", self.class.name)
+    }
+}
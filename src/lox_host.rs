@@ -0,0 +1,180 @@
+use std::any::Any;
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::engine::{self, Error, Value};
+use crate::interpreter::{Interpreter, InterpreterError};
+use crate::lox_callable::LoxCallable;
+use crate::lox_native::{Args, Arity, FromValue};
+use crate::token::{FunctionEnum, LiteralValue, Token};
+
+/// A Rust type scripts can use much like a class instance: reading and
+/// writing its properties, calling its methods, printing and comparing it
+pub trait HostObject: Any {
+    /// What the type is called in messages, and by `print` unless `describe` says otherwise
+    fn type_name(&self) -> &str;
+
+    /// Read a property, None if the object has no property called `name`
+    fn get(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    /// Write a property, objects are read only unless they override this
+    fn set(&mut self, name: &str, _value: Value) -> Result<(), Error> {
+        Err(Error::runtime(format!("Can't set property '{name}' on {}.", self.type_name())))
+    }
+
+    /// The arity of method `name`, None if there is no such method.
+    /// Properties returned by `get` shadow methods of the same name.
+    fn method(&self, _name: &str) -> Option<Arity> {
+        None
+    }
+
+    /// Run method `name`, which `method` has already accepted along with the
+    /// number of arguments
+    fn call_method(&mut self, name: &str, _args: &Args) -> Result<Value, Error> {
+        Err(Error::runtime(format!("Undefined method '{name}' on {}.", self.type_name())))
+    }
+
+    /// How `print` shows the object
+    fn describe(&self) -> String {
+        format!("{} instance", self.type_name())
+    }
+
+    /// Whether the object equals a different one, `==` is true for the same
+    /// object without asking. Downcast `other` through `Any` to compare contents.
+    fn equals(&self, _other: &dyn HostObject) -> bool {
+        false
+    }
+}
+
+/// A shared reference to a host object, cloning it refers to the same object
+#[derive(Clone)]
+pub struct HostRef(Rc<RefCell<dyn HostObject>>);
+
+/// A host object's method looked up by `object.name`, waiting to be called
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostMethod {
+    pub object: HostRef,
+    pub name: String,
+    pub arity: Arity,
+}
+
+impl HostRef {
+    pub fn new(object: impl HostObject) -> HostRef {
+        HostRef(Rc::new(RefCell::new(object)))
+    }
+
+    pub fn borrow(&self) -> Ref<'_, dyn HostObject> {
+        self.0.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, dyn HostObject> {
+        self.0.borrow_mut()
+    }
+
+    /// The object as its concrete type, None if it is another type or is
+    /// being mutated, as a method's own receiver is while the method runs
+    pub fn downcast_ref<T: HostObject>(&self) -> Option<Ref<'_, T>> {
+        let object = self.0.try_borrow().ok()?;
+        Ref::filter_map(object, |object| (object as &dyn Any).downcast_ref::<T>()).ok()
+    }
+
+    pub fn downcast_mut<T: HostObject>(&self) -> Option<RefMut<'_, T>> {
+        let object = self.0.try_borrow_mut().ok()?;
+        RefMut::filter_map(object, |object| (object as &mut dyn Any).downcast_mut::<T>()).ok()
+    }
+}
+
+impl FromValue for HostRef {
+    const EXPECTED: &'static str = "a host object";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Host(object) => { Some(object.clone()) }
+            _ => { None }
+        }
+    }
+}
+
+impl From<HostRef> for Value {
+    fn from(object: HostRef) -> Self {
+        Value::Host(object)
+    }
+}
+
+impl PartialEq for HostRef {
+    fn eq(&self, other: &Self) -> bool {
+        if Rc::ptr_eq(&self.0, &other.0) {
+            return true;
+        }
+        match (self.0.try_borrow(), other.0.try_borrow()) {
+            (Ok(object), Ok(other)) => { object.equals(&*other) }
+            _ => { false }
+        }
+    }
+}
+
+impl fmt::Debug for HostRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.try_borrow() {
+            Ok(object) => write!(f, "HostRef({})", object.type_name()),
+            Err(_) => write!(f, "HostRef(<borrowed>)"),
+        }
+    }
+}
+
+impl fmt::Display for HostRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.try_borrow() {
+            Ok(object) => write!(f, "{}", object.describe()),
+            Err(_) => write!(f, "<host object>"),
+        }
+    }
+}
+
+/// `object.name` on a host object, a property or else a method bound to the object
+pub fn get(interpreter: &mut Interpreter, object: &HostRef, name: &Token) -> Result<LiteralValue, InterpreterError> {
+    let (property, arity, type_name) = {
+        let borrowed = object.borrow();
+        (borrowed.get(&name.lexeme), borrowed.method(&name.lexeme), borrowed.type_name().to_string())
+    };
+    if let Some(property) = property {
+        return Ok(engine::to_lox(interpreter, &property)?);
+    }
+    match arity {
+        Some(arity) => {
+            let method = HostMethod { object: object.clone(), name: name.lexeme.clone(), arity };
+            Ok(LiteralValue::Function(FunctionEnum::Host(Rc::new(method))))
+        }
+        None => {
+            Err(InterpreterError::new(format!("Undefined property '{}' on {type_name}.", name.lexeme)))
+        }
+    }
+}
+
+/// `object.name = value` on a host object
+pub fn set(interpreter: &mut Interpreter, object: &HostRef, name: &Token, value: &LiteralValue) -> Result<(), InterpreterError> {
+    let value = engine::to_host(interpreter, value)?;
+    object.borrow_mut().set(&name.lexeme, value)?;
+    Ok(())
+}
+
+impl LoxCallable for HostMethod {
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<LiteralValue>) -> Result<LiteralValue, InterpreterError> {
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in &arguments {
+            values.push(engine::to_host(interpreter, argument)?);
+        }
+        let result = self.object.borrow_mut().call_method(&self.name, &Args::new(&self.name, &values))?;
+        Ok(engine::to_lox(interpreter, &result)?)
+    }
+
+    /// The fewest arguments the method takes, `Interpreter::call_value` checks the full `Arity`
+    fn arity(&self) -> Result<u8, InterpreterError> {
+        match self.arity {
+            Arity::Fixed(arity) | Arity::Range(arity, _) | Arity::Variadic(arity) => { Ok(arity) }
+        }
+    }
+}
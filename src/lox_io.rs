@@ -0,0 +1,114 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+
+use crate::interpreter::{Interpreter, InterpreterError};
+use crate::lox_native::{Arity, NativeRegistry};
+use crate::token::LiteralValue;
+
+/// Error for anything that would touch the filesystem while it is disabled
+pub fn sandbox_error(action: &str) -> InterpreterError {
+    InterpreterError::new(format!("Filesystem access is disabled, can't {action}."))
+}
+
+/// Add the file and stdin natives to `registry`. Failures are ordinary
+/// runtime errors so scripts can catch them.
+pub fn register(registry: &mut NativeRegistry) {
+    registry.register_builtin("read_file", Arity::Fixed(1), |interpreter, arguments| {
+        let path = path_arg(interpreter, "read_file", arguments)?;
+        match fs::read_to_string(path) {
+            Ok(contents) => { Ok(LiteralValue::StringValue(contents)) }
+            Err(err) => { Err(io_error("read", path, err)) }
+        }
+    });
+    registry.register_builtin("write_file", Arity::Fixed(2), |interpreter, arguments| {
+        let path = path_arg(interpreter, "write_file", arguments)?;
+        let contents = contents_arg("write_file", arguments)?;
+        match fs::write(path, contents) {
+            Ok(_) => { Ok(LiteralValue::None) }
+            Err(err) => { Err(io_error("write", path, err)) }
+        }
+    });
+    registry.register_builtin("append_file", Arity::Fixed(2), |interpreter, arguments| {
+        let path = path_arg(interpreter, "append_file", arguments)?;
+        let contents = contents_arg("append_file", arguments)?;
+        let appended = OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()));
+        match appended {
+            Ok(_) => { Ok(LiteralValue::None) }
+            Err(err) => { Err(io_error("append to", path, err)) }
+        }
+    });
+    registry.register_builtin("file_exists", Arity::Fixed(1), |interpreter, arguments| {
+        let path = path_arg(interpreter, "file_exists", arguments)?;
+        if fs::metadata(path).is_ok() {
+            Ok(LiteralValue::True)
+        } else {
+            Ok(LiteralValue::False)
+        }
+    });
+    // Reading stdin is allowed in the sandbox, only the filesystem is off limits
+    registry.register_builtin("read_line", Arity::Fixed(0), |_, _| {
+        // nil once stdin is exhausted, the line ending is dropped
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => { Ok(LiteralValue::None) }
+            Ok(_) => {
+                let len = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(len);
+                Ok(LiteralValue::StringValue(line))
+            }
+            Err(err) => { Err(InterpreterError::new(format!("Couldn't read from stdin: {err}."))) }
+        }
+    });
+    registry.register_builtin("read_lines", Arity::Fixed(1), |interpreter, arguments| {
+        let path = path_arg(interpreter, "read_lines", arguments)?;
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => { contents }
+            Err(err) => { return Err(io_error("read", path, err)); }
+        };
+        let lines = contents.lines().map(|line| LiteralValue::StringValue(line.to_string())).collect();
+        Ok(LiteralValue::List(interpreter.heap.alloc_list(lines)))
+    });
+    registry.register_builtin("list_dir", Arity::Fixed(1), |interpreter, arguments| {
+        let path = path_arg(interpreter, "list_dir", arguments)?;
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => { entries }
+            Err(err) => { return Err(io_error("list", path, err)); }
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(entry) => { names.push(entry.file_name().to_string_lossy().into_owned()); }
+                Err(err) => { return Err(io_error("list", path, err)); }
+            }
+        }
+        // read_dir order depends on the platform, sort so scripts behave the same everywhere
+        names.sort();
+        let names = names.into_iter().map(LiteralValue::StringValue).collect();
+        Ok(LiteralValue::List(interpreter.heap.alloc_list(names)))
+    });
+}
+
+/// The path a file native was given, once the sandbox has allowed touching the filesystem
+fn path_arg<'a>(interpreter: &Interpreter, name: &str, arguments: &'a [LiteralValue]) -> Result<&'a str, InterpreterError> {
+    if interpreter.sandboxed() {
+        return Err(sandbox_error(&format!("call {name}()")));
+    }
+    match &arguments[0] {
+        LiteralValue::StringValue(path) => { Ok(path) }
+        _ => { Err(InterpreterError::new(format!("{name}() expects a path string as its first argument."))) }
+    }
+}
+
+fn contents_arg<'a>(name: &str, arguments: &'a [LiteralValue]) -> Result<&'a str, InterpreterError> {
+    match &arguments[1] {
+        LiteralValue::StringValue(contents) => { Ok(contents) }
+        _ => { Err(InterpreterError::new(format!("{name}() expects a string as its second argument."))) }
+    }
+}
+
+fn io_error(action: &str, path: &str, err: io::Error) -> InterpreterError {
+    InterpreterError::new(format!("Couldn't {action} \"{path}\": {err}."))
+}
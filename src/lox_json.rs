@@ -0,0 +1,398 @@
+use crate::gc::{GcRef, Heap};
+use crate::interpreter::InterpreterError;
+use crate::lox_map::{LoxMap, MapKey};
+use crate::lox_native::{Arity, NativeRegistry};
+use crate::token::LiteralValue;
+
+// Deeper documents are rejected rather than risking the native stack
+const MAX_DEPTH: usize = 512;
+
+/// Add the JSON natives to `registry`. Objects become maps and arrays become
+/// lists, the other way round for `json_stringify`.
+pub fn register(registry: &mut NativeRegistry) {
+    registry.register_builtin("json_parse", Arity::Fixed(1), |interpreter, arguments| {
+        let text = match &arguments[0] {
+            LiteralValue::StringValue(text) => { text }
+            _ => { return Err(InterpreterError::new("json_parse() expects a string.".to_string())); }
+        };
+        let mut parser = JsonParser { chars: text.chars().collect(), current: 0, depth: 0, heap: &mut interpreter.heap };
+        parser.document()
+    });
+    registry.register_builtin("json_stringify", Arity::Range(1, 2), |interpreter, arguments| {
+        let indent = match arguments.get(1) {
+            None | Some(LiteralValue::None) => { None }
+            Some(LiteralValue::NumValue(num)) if num.fract() == 0.0 && (0.0..=10.0).contains(num) => {
+                Some(" ".repeat(*num as usize))
+            }
+            Some(LiteralValue::StringValue(indent)) => { Some(indent.clone()) }
+            _ => {
+                return Err(InterpreterError::new("json_stringify() indent must be nil, a string or a number from 0 to 10.".to_string()));
+            }
+        };
+        // As in JavaScript, an indent of 0 or "" gives the compact form
+        let indent = indent.filter(|indent| !indent.is_empty());
+        let mut writer = JsonWriter { heap: &interpreter.heap, indent, out: String::new(), visiting: Vec::new() };
+        writer.value(&arguments[0], 0)?;
+        Ok(LiteralValue::StringValue(writer.out))
+    });
+}
+
+struct JsonParser<'a> {
+    chars: Vec<char>,
+    current: usize,
+    depth: usize,
+    heap: &'a mut Heap,
+}
+
+impl JsonParser<'_> {
+    fn document(&mut self) -> Result<LiteralValue, InterpreterError> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        if self.current < self.chars.len() {
+            return Err(self.error("Unexpected text after the JSON value"));
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<LiteralValue, InterpreterError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => { self.object() }
+            Some('[') => { self.array() }
+            Some('"') => { Ok(LiteralValue::StringValue(self.string()?)) }
+            Some('t') => { self.keyword("true", LiteralValue::True) }
+            Some('f') => { self.keyword("false", LiteralValue::False) }
+            Some('n') => { self.keyword("null", LiteralValue::None) }
+            Some(c) if c == '-' || c.is_ascii_digit() => { self.number() }
+            Some(_) => { Err(self.error("Unexpected character")) }
+            None => { Err(self.error("Unexpected end of input")) }
+        }
+    }
+
+    fn object(&mut self) -> Result<LiteralValue, InterpreterError> {
+        self.enter()?;
+        self.current += 1;
+        let mut map = LoxMap::new();
+        self.skip_whitespace();
+        if !self.matches('}') {
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some('"') {
+                    return Err(self.error("Expected a string key"));
+                }
+                let key = self.string()?;
+                self.skip_whitespace();
+                if !self.matches(':') {
+                    return Err(self.error("Expected ':' after an object key"));
+                }
+                let value = self.value()?;
+                map.insert(MapKey::Str(key), value);
+                self.skip_whitespace();
+                if self.matches('}') {
+                    break;
+                }
+                if !self.matches(',') {
+                    return Err(self.error("Expected ',' or '}' in an object"));
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(LiteralValue::Map(self.heap.alloc_map(map)))
+    }
+
+    fn array(&mut self) -> Result<LiteralValue, InterpreterError> {
+        self.enter()?;
+        self.current += 1;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if !self.matches(']') {
+            loop {
+                elements.push(self.value()?);
+                self.skip_whitespace();
+                if self.matches(']') {
+                    break;
+                }
+                if !self.matches(',') {
+                    return Err(self.error("Expected ',' or ']' in an array"));
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(LiteralValue::List(self.heap.alloc_list(elements)))
+    }
+
+    fn string(&mut self) -> Result<String, InterpreterError> {
+        self.current += 1;
+        let mut string = String::new();
+        loop {
+            let c = match self.advance() {
+                Some(c) => { c }
+                None => { return Err(self.error("Unterminated string")); }
+            };
+            match c {
+                '"' => { return Ok(string); }
+                '\\' => {
+                    let escaped = match self.advance() {
+                        Some('"') => { '"' }
+                        Some('\\') => { '\\' }
+                        Some('/') => { '/' }
+                        Some('b') => { '\u{8}' }
+                        Some('f') => { '\u{c}' }
+                        Some('n') => { '\n' }
+                        Some('r') => { '\r' }
+                        Some('t') => { '\t' }
+                        Some('u') => { self.unicode_escape()? }
+                        _ => { return Err(self.error("Invalid escape in string")); }
+                    };
+                    string.push(escaped);
+                }
+                c if (c as u32) < 0x20 => { return Err(self.error("Control character in string")); }
+                c => { string.push(c); }
+            }
+        }
+    }
+
+    /// The code point after `\u`, joining UTF-16 surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, InterpreterError> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.advance() != Some('\\') || self.advance() != Some('u') {
+                return Err(self.error("Unpaired surrogate in string"));
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("Unpaired surrogate in string"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, InterpreterError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.advance().and_then(|c| c.to_digit(16)) {
+                Some(digit) => { code = code * 16 + digit; }
+                None => { return Err(self.error("Invalid unicode escape")); }
+            }
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<LiteralValue, InterpreterError> {
+        let start = self.current;
+        self.matches('-');
+        // No leading zeros, so "0" can't be followed by more digits
+        if !self.matches('0') && !self.digits() {
+            return Err(self.error("Invalid number"));
+        }
+        if self.matches('.') && !self.digits() {
+            return Err(self.error("Expected digits after '.'"));
+        }
+        if self.matches('e') || self.matches('E') {
+            if !self.matches('+') {
+                self.matches('-');
+            }
+            if !self.digits() {
+                return Err(self.error("Expected digits in exponent"));
+            }
+        }
+        let text: String = self.chars[start..self.current].iter().collect();
+        match text.parse::<f64>() {
+            // Too big for a double parses as inf, which JSON has no way to write back
+            Ok(num) if num.is_infinite() => { Err(self.error("Number out of range")) }
+            Ok(num) => { Ok(LiteralValue::NumValue(num)) }
+            Err(_) => { Err(self.error("Invalid number")) }
+        }
+    }
+
+    /// Consume a run of digits, returning whether there was at least one
+    fn digits(&mut self) -> bool {
+        let start = self.current;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.current += 1;
+        }
+        self.current > start
+    }
+
+    fn keyword(&mut self, word: &str, value: LiteralValue) -> Result<LiteralValue, InterpreterError> {
+        for expected in word.chars() {
+            if self.advance() != Some(expected) {
+                return Err(self.error("Unexpected character"));
+            }
+        }
+        Ok(value)
+    }
+
+    fn enter(&mut self) -> Result<(), InterpreterError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("JSON is nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.current += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.current).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.current += 1;
+        Some(c)
+    }
+
+    fn matches(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Errors point at the line and column of the document being parsed
+    fn error(&self, message: &str) -> InterpreterError {
+        let before = &self.chars[..self.current.min(self.chars.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        InterpreterError::new(format!("json_parse(): {message} at line {line}, column {column}."))
+    }
+}
+
+struct JsonWriter<'a> {
+    heap: &'a Heap,
+    indent: Option<String>,
+    out: String,
+    // Lists and maps currently being written, seeing one again means a cycle
+    visiting: Vec<GcRef>,
+}
+
+impl JsonWriter<'_> {
+    fn value(&mut self, value: &LiteralValue, depth: usize) -> Result<(), InterpreterError> {
+        match value {
+            LiteralValue::None => { self.out.push_str("null"); }
+            LiteralValue::True => { self.out.push_str("true"); }
+            LiteralValue::False => { self.out.push_str("false"); }
+            LiteralValue::NumValue(num) => {
+                if !num.is_finite() {
+                    return Err(InterpreterError::new(format!("json_stringify() can't represent the number {num}.")));
+                }
+                self.number(*num);
+            }
+            LiteralValue::StringValue(string) => { self.string(string); }
+            LiteralValue::List(list) => {
+                self.enter(*list)?;
+                let heap = self.heap;
+                let elements = heap.list(*list);
+                self.out.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(',');
+                    }
+                    self.newline(depth + 1);
+                    self.value(element, depth + 1)?;
+                }
+                if !elements.is_empty() {
+                    self.newline(depth);
+                }
+                self.out.push(']');
+                self.visiting.pop();
+            }
+            LiteralValue::Map(map) => {
+                self.enter(*map)?;
+                let heap = self.heap;
+                let entries = heap.map(*map).entries();
+                self.out.push('{');
+                for (i, (key, element)) in entries.iter().enumerate() {
+                    let key = match key {
+                        MapKey::Str(key) => { key }
+                        _ => {
+                            return Err(InterpreterError::new(format!("json_stringify() needs string keys, got {}.", key.to_value())));
+                        }
+                    };
+                    if i > 0 {
+                        self.out.push(',');
+                    }
+                    self.newline(depth + 1);
+                    self.string(key);
+                    self.out.push(':');
+                    if self.indent.is_some() {
+                        self.out.push(' ');
+                    }
+                    self.value(element, depth + 1)?;
+                }
+                if !entries.is_empty() {
+                    self.newline(depth);
+                }
+                self.out.push('}');
+                self.visiting.pop();
+            }
+            LiteralValue::Function(_) | LiteralValue::Class(_) | LiteralValue::IdentifierValue(_) | LiteralValue::Host(_) => {
+                return Err(InterpreterError::new(format!("json_stringify() can't serialize {value}.")));
+            }
+            LiteralValue::Instance(_) => {
+                return Err(InterpreterError::new("json_stringify() can't serialize class instances.".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, reference: GcRef) -> Result<(), InterpreterError> {
+        if self.visiting.contains(&reference) {
+            return Err(InterpreterError::new("json_stringify() can't serialize a cyclic structure.".to_string()));
+        }
+        self.visiting.push(reference);
+        Ok(())
+    }
+
+    /// Numbers are written the way JavaScript does, switching to an exponent
+    /// for very large and very small magnitudes
+    fn number(&mut self, num: f64) {
+        let magnitude = num.abs();
+        if magnitude >= 1e21 || (magnitude != 0.0 && magnitude < 1e-6) {
+            let formatted = format!("{num:e}");
+            match formatted.split_once('e') {
+                Some((mantissa, exponent)) if !exponent.starts_with('-') => {
+                    self.out.push_str(&format!("{mantissa}e+{exponent}"));
+                }
+                _ => { self.out.push_str(&formatted); }
+            }
+        } else {
+            self.out.push_str(&num.to_string());
+        }
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if let Some(indent) = &self.indent {
+            self.out.push('\n');
+            self.out.push_str(&indent.repeat(depth));
+        }
+    }
+
+    fn string(&mut self, string: &str) {
+        self.out.push('"');
+        for c in string.chars() {
+            match c {
+                '"' => { self.out.push_str("\\\""); }
+                '\\' => { self.out.push_str("\\\\"); }
+                '\n' => { self.out.push_str("\\n"); }
+                '\r' => { self.out.push_str("\\r"); }
+                '\t' => { self.out.push_str("\\t"); }
+                '\u{8}' => { self.out.push_str("\\b"); }
+                '\u{c}' => { self.out.push_str("\\f"); }
+                c if (c as u32) < 0x20 => { self.out.push_str(&format!("\\u{:04x}", c as u32)); }
+                c => { self.out.push(c); }
+            }
+        }
+        self.out.push('"');
+    }
+}
@@ -0,0 +1,130 @@
+use std::cmp::Ordering;
+
+use crate::gc::{GcRef, Heap};
+use crate::interpreter::InterpreterError;
+use crate::lox_native::{Arity, NativeRegistry};
+use crate::token::LiteralValue;
+
+/// Turn a Lox index into a position in a list of `len` elements. Negative
+/// indices count back from the end, `allow_end` also accepts `len` itself
+/// for natives that insert after the last element.
+pub fn position(index: &LiteralValue, len: usize, allow_end: bool) -> Result<usize, InterpreterError> {
+    let index = match index {
+        LiteralValue::NumValue(num) => { *num }
+        _ => { return Err(InterpreterError::new("List index must be a number.".to_string())); }
+    };
+    if index.fract() != 0.0 {
+        return Err(InterpreterError::new(format!("List index must be a whole number, got {index}.")));
+    }
+    let position = if index < 0.0 { index + len as f64 } else { index };
+    let limit = if allow_end { len + 1 } else { len };
+    if position < 0.0 || position >= limit as f64 {
+        return Err(InterpreterError::new(format!("Index {index} is out of bounds for a list of length {len}.")));
+    }
+    Ok(position as usize)
+}
+
+pub fn get_index(heap: &Heap, list: GcRef, index: &LiteralValue) -> Result<LiteralValue, InterpreterError> {
+    let elements = heap.list(list);
+    let position = position(index, elements.len(), false)?;
+    Ok(elements[position].clone())
+}
+
+pub fn set_index(heap: &mut Heap, list: GcRef, index: &LiteralValue, value: LiteralValue) -> Result<(), InterpreterError> {
+    let elements = heap.list_mut(list);
+    let position = position(index, elements.len(), false)?;
+    elements[position] = value;
+    Ok(())
+}
+
+/// Add the list natives to `registry`, the list is always the first argument
+pub fn register(registry: &mut NativeRegistry) {
+    registry.register_builtin("len", Arity::Fixed(1), |interpreter, arguments| {
+        // Strings and maps have a length too, strings counted in characters
+        let len = match &arguments[0] {
+            LiteralValue::StringValue(string) => { string.chars().count() }
+            LiteralValue::Map(map) => { interpreter.heap.map(*map).entries().len() }
+            _ => { interpreter.heap.list(list_arg("len", arguments)?).len() }
+        };
+        Ok(LiteralValue::NumValue(len as f64))
+    });
+    registry.register_builtin("push", Arity::Fixed(2), |interpreter, arguments| {
+        let list = list_arg("push", arguments)?;
+        interpreter.heap.list_mut(list).push(arguments[1].clone());
+        Ok(LiteralValue::None)
+    });
+    registry.register_builtin("pop", Arity::Fixed(1), |interpreter, arguments| {
+        let list = list_arg("pop", arguments)?;
+        match interpreter.heap.list_mut(list).pop() {
+            Some(value) => { Ok(value) }
+            None => { Err(InterpreterError::new("Can't pop from an empty list.".to_string())) }
+        }
+    });
+    registry.register_builtin("insert", Arity::Fixed(3), |interpreter, arguments| {
+        let elements = interpreter.heap.list_mut(list_arg("insert", arguments)?);
+        let position = position(&arguments[1], elements.len(), true)?;
+        elements.insert(position, arguments[2].clone());
+        Ok(LiteralValue::None)
+    });
+    registry.register_builtin("remove", Arity::Fixed(2), |interpreter, arguments| {
+        let elements = interpreter.heap.list_mut(list_arg("remove", arguments)?);
+        let position = position(&arguments[1], elements.len(), false)?;
+        Ok(elements.remove(position))
+    });
+    registry.register_builtin("slice", Arity::Fixed(3), |interpreter, arguments| {
+        let elements = interpreter.heap.list(list_arg("slice", arguments)?);
+        let start = slice_bound(&arguments[1], elements.len(), 0)?;
+        let end = slice_bound(&arguments[2], elements.len(), elements.len())?;
+        let sliced = if start < end { elements[start..end].to_vec() } else { Vec::new() };
+        Ok(LiteralValue::List(interpreter.heap.alloc_list(sliced)))
+    });
+    registry.register_builtin("sort", Arity::Fixed(1), |interpreter, arguments| {
+        let elements = interpreter.heap.list_mut(list_arg("sort", arguments)?);
+        if elements.iter().all(|value| matches!(value, LiteralValue::NumValue(_))) {
+            elements.sort_by(|a, b| match (a, b) {
+                (LiteralValue::NumValue(a), LiteralValue::NumValue(b)) => { a.total_cmp(b) }
+                _ => { Ordering::Equal }
+            });
+        } else if elements.iter().all(|value| matches!(value, LiteralValue::StringValue(_))) {
+            elements.sort_by(|a, b| match (a, b) {
+                (LiteralValue::StringValue(a), LiteralValue::StringValue(b)) => { a.cmp(b) }
+                _ => { Ordering::Equal }
+            });
+        } else {
+            return Err(InterpreterError::new("sort() needs a list of only numbers or only strings.".to_string()));
+        }
+        Ok(LiteralValue::None)
+    });
+    registry.register_builtin("reverse", Arity::Fixed(1), |interpreter, arguments| {
+        interpreter.heap.list_mut(list_arg("reverse", arguments)?).reverse();
+        Ok(LiteralValue::None)
+    });
+    registry.register_builtin("contains", Arity::Fixed(2), |interpreter, arguments| {
+        if interpreter.heap.list(list_arg("contains", arguments)?).contains(&arguments[1]) {
+            Ok(LiteralValue::True)
+        } else {
+            Ok(LiteralValue::False)
+        }
+    });
+}
+
+fn list_arg(name: &str, arguments: &[LiteralValue]) -> Result<GcRef, InterpreterError> {
+    match arguments.first() {
+        Some(LiteralValue::List(list)) => { Ok(*list) }
+        _ => { Err(InterpreterError::new(format!("{name}() expects a list as its first argument."))) }
+    }
+}
+
+/// Slice bounds are clamped to the list instead of erroring, nil means the default
+fn slice_bound(bound: &LiteralValue, len: usize, default: usize) -> Result<usize, InterpreterError> {
+    match bound {
+        LiteralValue::None => { Ok(default) }
+        LiteralValue::NumValue(num) if num.fract() == 0.0 => {
+            let position = if *num < 0.0 { num + len as f64 } else { *num };
+            Ok(position.clamp(0.0, len as f64) as usize)
+        }
+        _ => {
+            Err(InterpreterError::new("slice() bounds must be whole numbers or nil.".to_string()))
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::gc::{GcRef, Heap};
+use crate::interpreter::InterpreterError;
+use crate::lox_native::{Arity, NativeRegistry};
+use crate::token::LiteralValue;
+
+/// The Lox values that can be used as map keys, numbers are stored by their
+/// bits so they can be hashed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    Number(u64),
+    Str(String),
+}
+
+/// A map that remembers the order keys were first inserted in
+#[derive(Debug, Clone, Default)]
+pub struct LoxMap {
+    entries: Vec<(MapKey, LiteralValue)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl MapKey {
+    pub fn from_value(value: &LiteralValue) -> Result<MapKey, InterpreterError> {
+        match value {
+            LiteralValue::None => { Ok(MapKey::Nil) }
+            LiteralValue::True => { Ok(MapKey::Bool(true)) }
+            LiteralValue::False => { Ok(MapKey::Bool(false)) }
+            LiteralValue::StringValue(s) => { Ok(MapKey::Str(s.clone())) }
+            LiteralValue::NumValue(num) => {
+                if num.is_nan() {
+                    return Err(InterpreterError::new("NaN can't be used as a map key.".to_string()));
+                }
+                // 0 and -0 compare equal so they must be the same key
+                let num = if *num == 0.0 { 0.0 } else { *num };
+                Ok(MapKey::Number(num.to_bits()))
+            }
+            _ => {
+                Err(InterpreterError::new("Map keys must be strings, numbers, booleans or nil.".to_string()))
+            }
+        }
+    }
+
+    pub fn to_value(&self) -> LiteralValue {
+        match self {
+            MapKey::Nil => { LiteralValue::None }
+            MapKey::Bool(true) => { LiteralValue::True }
+            MapKey::Bool(false) => { LiteralValue::False }
+            MapKey::Number(bits) => { LiteralValue::NumValue(f64::from_bits(*bits)) }
+            MapKey::Str(s) => { LiteralValue::StringValue(s.clone()) }
+        }
+    }
+}
+
+impl LoxMap {
+    pub fn new() -> LoxMap {
+        LoxMap::default()
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&LiteralValue> {
+        self.positions.get(key).map(|position| &self.entries[*position].1)
+    }
+
+    /// Overwriting a key keeps its original position
+    pub fn insert(&mut self, key: MapKey, value: LiteralValue) {
+        match self.positions.get(&key) {
+            Some(position) => { self.entries[*position].1 = value; }
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<LiteralValue> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (key, _) in &self.entries[position..] {
+            if let Some(later) = self.positions.get_mut(key) {
+                *later -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn entries(&self) -> &Vec<(MapKey, LiteralValue)> {
+        &self.entries
+    }
+}
+
+pub fn get_key(heap: &Heap, map: GcRef, key: &LiteralValue) -> Result<LiteralValue, InterpreterError> {
+    match heap.map(map).get(&MapKey::from_value(key)?) {
+        Some(value) => { Ok(value.clone()) }
+        None => {
+            let shown = match key {
+                LiteralValue::StringValue(s) => { format!("\"{s}\"") }
+                _ => { key.to_string() }
+            };
+            Err(InterpreterError::new(format!("Key {shown} is not in the map.")))
+        }
+    }
+}
+
+pub fn set_key(heap: &mut Heap, map: GcRef, key: &LiteralValue, value: LiteralValue) -> Result<(), InterpreterError> {
+    let key = MapKey::from_value(key)?;
+    heap.map_mut(map).insert(key, value);
+    Ok(())
+}
+
+/// Add the natives that work on maps to `registry`, the map is always the
+/// first argument. `len` is shared with lists and defined with them.
+pub fn register(registry: &mut NativeRegistry) {
+    registry.register_builtin("keys", Arity::Fixed(1), |interpreter, arguments| {
+        let map = map_arg("keys", arguments)?;
+        let keys = interpreter.heap.map(map).entries().iter().map(|(key, _)| key.to_value()).collect();
+        Ok(LiteralValue::List(interpreter.heap.alloc_list(keys)))
+    });
+    registry.register_builtin("values", Arity::Fixed(1), |interpreter, arguments| {
+        let map = map_arg("values", arguments)?;
+        let values = interpreter.heap.map(map).entries().iter().map(|(_, value)| value.clone()).collect();
+        Ok(LiteralValue::List(interpreter.heap.alloc_list(values)))
+    });
+    registry.register_builtin("has", Arity::Fixed(2), |interpreter, arguments| {
+        let map = map_arg("has", arguments)?;
+        let key = MapKey::from_value(&arguments[1])?;
+        match interpreter.heap.map(map).get(&key) {
+            Some(_) => { Ok(LiteralValue::True) }
+            None => { Ok(LiteralValue::False) }
+        }
+    });
+    registry.register_builtin("delete", Arity::Fixed(2), |interpreter, arguments| {
+        let map = map_arg("delete", arguments)?;
+        let key = MapKey::from_value(&arguments[1])?;
+        match interpreter.heap.map_mut(map).remove(&key) {
+            Some(_) => { Ok(LiteralValue::True) }
+            None => { Ok(LiteralValue::False) }
+        }
+    });
+}
+
+fn map_arg(name: &str, arguments: &[LiteralValue]) -> Result<GcRef, InterpreterError> {
+    match arguments.first() {
+        Some(LiteralValue::Map(map)) => { Ok(*map) }
+        _ => { Err(InterpreterError::new(format!("{name}() expects a map as its first argument."))) }
+    }
+}
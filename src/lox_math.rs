@@ -0,0 +1,74 @@
+use std::f64::consts;
+
+use crate::engine::{Error, Value};
+use crate::lox_native::{Args, Arity, NativeRegistry};
+use crate::token::LiteralValue;
+
+/// Math constants, defined as plain global numbers next to the natives
+pub const CONSTANTS: [(&str, f64); 4] = [
+    ("pi", consts::PI),
+    ("e", consts::E),
+    ("inf", f64::INFINITY),
+    ("nan", f64::NAN),
+];
+
+type Unary = fn(f64) -> f64;
+type Binary = fn(f64, f64) -> f64;
+
+const UNARY: [(&str, Unary); 9] = [
+    ("sqrt", f64::sqrt),
+    ("abs", f64::abs),
+    ("floor", f64::floor),
+    ("ceil", f64::ceil),
+    ("round", f64::round),
+    ("sin", f64::sin),
+    ("cos", f64::cos),
+    ("tan", f64::tan),
+    ("exp", f64::exp),
+];
+
+const BINARY: [(&str, Binary); 4] = [
+    ("pow", f64::powf),
+    // f64::min and f64::max ignore a NaN operand, but a NaN in means the result is meaningless
+    ("min", |lhs, rhs| if lhs.is_nan() || rhs.is_nan() { f64::NAN } else { lhs.min(rhs) }),
+    ("max", |lhs, rhs| if lhs.is_nan() || rhs.is_nan() { f64::NAN } else { lhs.max(rhs) }),
+    ("atan2", f64::atan2),
+];
+
+/// Add the math natives to `registry`. They only take numbers, so the VM
+/// can call them too.
+pub fn register(registry: &mut NativeRegistry) {
+    for (name, function) in UNARY {
+        registry.register(name, Arity::Fixed(1), move |args| {
+            let numbers = args.rest::<f64>(0)?;
+            checked(args, &numbers, function(numbers[0]))
+        });
+    }
+    for (name, function) in BINARY {
+        registry.register(name, Arity::Fixed(2), move |args| {
+            let numbers = args.rest::<f64>(0)?;
+            checked(args, &numbers, function(numbers[0], numbers[1]))
+        });
+    }
+    registry.register("log", Arity::Fixed(1), |args| {
+        let numbers = args.rest::<f64>(0)?;
+        // ln(0) would quietly give -inf, which is almost always a bug in the script
+        if numbers[0] <= 0.0 {
+            return Err(domain_error(args, &numbers));
+        }
+        checked(args, &numbers, numbers[0].ln())
+    });
+}
+
+/// NaN is only passed through, never made up from ordinary numbers
+fn checked(args: &Args, numbers: &[f64], result: f64) -> Result<Value, Error> {
+    if result.is_nan() && !numbers.iter().any(|num| num.is_nan()) {
+        return Err(domain_error(args, numbers));
+    }
+    Ok(Value::Number(result))
+}
+
+fn domain_error(args: &Args, numbers: &[f64]) -> Error {
+    let arguments: Vec<String> = numbers.iter().map(|num| LiteralValue::NumValue(*num).to_string()).collect();
+    Error::runtime(format!("{}() is undefined for {}.", args.name(), arguments.join(", ")))
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::ast::Stmt;
+use crate::interpreter::{InterpreterError, Raised};
+use crate::lox_class::LoxInstance;
+use crate::token::Span;
+
+/// A file loaded by `import`, kept so errors inside it can be shown
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+/// Finds, caches and keeps track of the modules loaded by `import`
+pub struct ModuleLoader {
+    // Directories searched after the importing file's own, from LOXPATH
+    search_path: Vec<PathBuf>,
+    // Source id n is stored at index n - 1, 0 is the main program
+    sources: Vec<SourceFile>,
+    cache: HashMap<PathBuf, LoxInstance>,
+    // Files currently being executed, the innermost importer is last
+    loading: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> ModuleLoader {
+        let search_path = match env::var_os("LOXPATH") {
+            Some(paths) => { env::split_paths(&paths).collect() }
+            None => { Vec::new() }
+        };
+        ModuleLoader {
+            search_path,
+            sources: Vec::new(),
+            cache: HashMap::new(),
+            loading: Vec::new(),
+        }
+    }
+
+    /// Imports in the main program are resolved relative to its file
+    pub fn set_main_file(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.loading.clear();
+        self.loading.push(path);
+    }
+
+    /// Find the file for an import, trying the directory of the importing
+    /// file and then the search path, with and without a `.lox` extension
+    pub fn resolve(&self, requested: &str) -> Result<PathBuf, InterpreterError> {
+        let mut candidates = vec![PathBuf::from(requested)];
+        if Path::new(requested).extension().is_none() {
+            candidates.push(PathBuf::from(format!("{requested}.lox")));
+        }
+
+        let importer_dir = match self.loading.last().and_then(|path| path.parent()) {
+            Some(dir) => { dir.to_path_buf() }
+            None => { PathBuf::from(".") }
+        };
+        let mut dirs = vec![importer_dir];
+        dirs.extend(self.search_path.iter().cloned());
+
+        for dir in &dirs {
+            for candidate in &candidates {
+                let path = dir.join(candidate);
+                if path.is_file() {
+                    return path.canonicalize().map_err(|err| {
+                        InterpreterError::new(format!("Couldn't resolve module \"{requested}\": {err}"))
+                    });
+                }
+            }
+        }
+        Err(InterpreterError::new(format!("Couldn't find module \"{requested}\".")))
+    }
+
+    pub fn cached(&self, path: &Path) -> Option<LoxInstance> {
+        self.cache.get(path).cloned()
+    }
+
+    /// Mark a module as being executed, failing if it is already part of the
+    /// chain of imports that led here
+    pub fn begin(&mut self, path: &Path) -> Result<(), InterpreterError> {
+        if let Some(start) = self.loading.iter().position(|loading| loading == path) {
+            let mut chain: Vec<String> = self.loading[start..].iter().map(|path| ModuleLoader::display_name(path)).collect();
+            chain.push(ModuleLoader::display_name(path));
+            return Err(InterpreterError::new(format!("Import cycle: {}.", chain.join(" -> "))));
+        }
+        self.loading.push(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Done executing a module, caching it if it loaded successfully
+    pub fn finish(&mut self, path: &Path, module: Option<LoxInstance>) {
+        self.loading.pop();
+        if let Some(module) = module {
+            self.cache.insert(path.to_path_buf(), module);
+        }
+    }
+
+    /// Keep a module's text and hand out the id its spans are tagged with
+    pub fn add_source(&mut self, path: &Path, text: String) -> u32 {
+        self.sources.push(SourceFile { name: ModuleLoader::display_name(path), text });
+        self.sources.len() as u32
+    }
+
+    pub fn source(&self, source_id: u32) -> Option<&SourceFile> {
+        match source_id {
+            0 => { None }
+            _ => { self.sources.get(source_id as usize - 1) }
+        }
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = &LoxInstance> {
+        self.cache.values()
+    }
+
+    /// Paths are shown relative to the working directory when possible
+    fn display_name(path: &Path) -> String {
+        let relative = env::current_dir().ok().and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf));
+        match relative {
+            Some(relative) => { relative.display().to_string() }
+            None => { path.display().to_string() }
+        }
+    }
+}
+
+/// Names of the top level declarations marked with `export`, in order
+pub fn exported_names(statements: &[Stmt]) -> Vec<String> {
+    let mut names = Vec::new();
+    for statement in statements {
+        if let Stmt::Export { declaration, .. } = statement {
+            match declaration.as_ref() {
+                Stmt::Class { name, .. } | Stmt::Function { name, .. } | Stmt::Variable { name, .. } => {
+                    names.push(name.lexeme.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+    names
+}
+
+/// A scan, parse or resolve error in an imported file, pointing into that file
+pub fn static_error(message: &str, span: Span) -> InterpreterError {
+    let mut err = InterpreterError::new(message.to_string());
+    err.span = Some(span);
+    err.raised = Some(Raised::Static);
+    err
+}
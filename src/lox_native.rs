@@ -0,0 +1,326 @@
+use std::env;
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::engine::{Error, Value};
+use crate::interpreter::{Interpreter, InterpreterError};
+use crate::lox_math;
+use crate::lox_string;
+use crate::token::LiteralValue;
+
+/// How many arguments a registered native accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Fixed(u8),
+    /// Inclusive bounds, missing arguments are simply not passed
+    Range(u8, u8),
+    /// At least this many, with no upper bound
+    Variadic(u8),
+}
+
+/// A Rust closure callable from Lox
+pub type NativeFn = Rc<dyn Fn(&Args) -> Result<Value, Error>>;
+
+/// A builtin that works on the tree-walker's own values, so lists and maps
+/// are used in place instead of being copied out like a `NativeFn` gets them
+pub(crate) type BuiltinFn = fn(&mut Interpreter, &[LiteralValue]) -> Result<LiteralValue, InterpreterError>;
+
+/// What a native runs when it is called
+#[derive(Clone)]
+pub(crate) enum NativeBody {
+    Host(NativeFn),
+    Builtin(BuiltinFn),
+}
+
+/// Natives defined in Rust, the builtins and any the embedding host adds.
+/// Each one is bound to a global under its name.
+#[derive(Clone)]
+pub struct NativeRegistry {
+    natives: Vec<(String, Arity, NativeBody)>,
+}
+
+/// The arguments a native was called with, along with helpers that convert
+/// them and report mistakes in terms of the native's name
+pub struct Args<'a> {
+    name: &'a str,
+    values: &'a [Value],
+}
+
+/// Rust types a Lox argument can be converted to
+pub trait FromValue: Sized {
+    /// What the type is called in error messages
+    const EXPECTED: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Fixed(arity) => { count == arity as usize }
+            Arity::Range(min, max) => { (min as usize..=max as usize).contains(&count) }
+            Arity::Variadic(min) => { count >= min as usize }
+        }
+    }
+}
+
+impl NativeRegistry {
+    /// A registry holding the builtins both backends share, the tree-walker
+    /// and the VM each add the ones that depend on how they store values
+    pub fn new() -> NativeRegistry {
+        let mut registry = NativeRegistry { natives: Vec::new() };
+        registry.register("clock", Arity::Fixed(0), |_| {
+            let now = SystemTime::now();
+            let now_in_ms = now.duration_since(SystemTime::UNIX_EPOCH).expect("Error Getting Time").as_millis();
+            Ok(Value::Number(now_in_ms as f64))
+        });
+        registry.register("getenv", Arity::Fixed(1), |args| {
+            Ok(env::var(args.get::<String>(0)?).ok().into())
+        });
+        registry.register("exit", Arity::Fixed(1), |args| {
+            let code = args.get::<i64>(0)?;
+            match u8::try_from(code) {
+                Ok(code) => { Err(Error::Exit(code)) }
+                Err(_) => { Err(Error::runtime(format!("exit() expects a status code from 0 to 255, got {code}."))) }
+            }
+        });
+        lox_math::register(&mut registry);
+        lox_string::register(&mut registry);
+        registry
+    }
+
+    /// Add a native, returning the id that `FunctionEnum::Native` refers to it by
+    pub fn register(&mut self, name: &str, arity: Arity, function: impl Fn(&Args) -> Result<Value, Error> + 'static) -> usize {
+        self.natives.push((name.to_string(), arity, NativeBody::Host(Rc::new(function))));
+        self.natives.len() - 1
+    }
+
+    /// Add a builtin that only the tree-walker can call
+    pub(crate) fn register_builtin(&mut self, name: &str, arity: Arity, function: BuiltinFn) -> usize {
+        self.natives.push((name.to_string(), arity, NativeBody::Builtin(function)));
+        self.natives.len() - 1
+    }
+
+    /// Every native as (id, name), in the order they were registered
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.natives.iter().enumerate().map(|(id, (name, _, _))| (id, name.as_str()))
+    }
+
+    pub fn name(&self, id: usize) -> &str {
+        &self.natives[id].0
+    }
+
+    pub fn arity(&self, id: usize) -> Arity {
+        self.natives[id].1
+    }
+
+    pub(crate) fn body(&self, id: usize) -> NativeBody {
+        self.natives[id].2.clone()
+    }
+
+    /// Run a native on host values, the caller has already checked the arity
+    pub fn call(&self, id: usize, values: &[Value]) -> Result<Value, Error> {
+        match &self.natives[id] {
+            (name, _, NativeBody::Host(function)) => { function(&Args::new(name, values)) }
+            (name, _, NativeBody::Builtin(_)) => {
+                Err(Error::runtime(format!("{name}() is only available in the tree-walking interpreter.")))
+            }
+        }
+    }
+
+    /// Run a native on plain values, for the VM, which has no heap to copy
+    /// lists and maps out of
+    pub fn call_scalars(&self, id: usize, arguments: Vec<LiteralValue>) -> Result<LiteralValue, InterpreterError> {
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in &arguments {
+            match argument {
+                LiteralValue::None => { values.push(Value::Nil); }
+                LiteralValue::True => { values.push(Value::Bool(true)); }
+                LiteralValue::False => { values.push(Value::Bool(false)); }
+                LiteralValue::NumValue(num) => { values.push(Value::Number(*num)); }
+                LiteralValue::StringValue(s) => { values.push(Value::String(s.clone())); }
+                _ => {
+                    return Err(InterpreterError::new(format!("{}() only takes plain values in the VM.", self.name(id))));
+                }
+            }
+        }
+        match self.call(id, &values)? {
+            Value::Nil => { Ok(LiteralValue::None) }
+            Value::Bool(true) => { Ok(LiteralValue::True) }
+            Value::Bool(false) => { Ok(LiteralValue::False) }
+            Value::Number(num) => { Ok(LiteralValue::NumValue(num)) }
+            Value::String(s) => { Ok(LiteralValue::StringValue(s)) }
+            _ => {
+                Err(InterpreterError::new(format!("{}() can only return plain values in the VM.", self.name(id))))
+            }
+        }
+    }
+}
+
+impl Default for NativeRegistry {
+    fn default() -> Self {
+        NativeRegistry::new()
+    }
+}
+
+impl<'a> Args<'a> {
+    pub(crate) fn new(name: &'a str, values: &'a [Value]) -> Args<'a> {
+        Args { name, values }
+    }
+
+    /// Name the native was registered under
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[Value] {
+        self.values
+    }
+
+    /// Convert argument `index`, failing if it is missing or has the wrong type
+    pub fn get<T: FromValue>(&self, index: usize) -> Result<T, Error> {
+        match self.values.get(index) {
+            Some(value) => { self.convert(index, value) }
+            None => { Err(Error::runtime(format!("{}() is missing argument {}.", self.name, index + 1))) }
+        }
+    }
+
+    /// Like `get`, but a missing or nil argument gives None
+    pub fn optional<T: FromValue>(&self, index: usize) -> Result<Option<T>, Error> {
+        match self.values.get(index) {
+            None | Some(Value::Nil) => { Ok(None) }
+            Some(value) => { self.convert(index, value).map(Some) }
+        }
+    }
+
+    /// Convert every argument from `start` on, for variadic natives
+    pub fn rest<T: FromValue>(&self, start: usize) -> Result<Vec<T>, Error> {
+        let mut converted = Vec::new();
+        for (index, value) in self.values.iter().enumerate().skip(start) {
+            converted.push(self.convert(index, value)?);
+        }
+        Ok(converted)
+    }
+
+    fn convert<T: FromValue>(&self, index: usize, value: &Value) -> Result<T, Error> {
+        T::from_value(value).ok_or_else(|| {
+            Error::runtime(format!("{}() expects {} as argument {}, got {value}.", self.name, T::EXPECTED, index + 1))
+        })
+    }
+}
+
+impl FromValue for Value {
+    const EXPECTED: &'static str = "a value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(num) => { Some(*num) }
+            _ => { None }
+        }
+    }
+}
+
+impl FromValue for i64 {
+    const EXPECTED: &'static str = "a whole number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(num) if num.fract() == 0.0 && num.abs() < i64::MAX as f64 => { Some(*num as i64) }
+            _ => { None }
+        }
+    }
+}
+
+impl FromValue for bool {
+    const EXPECTED: &'static str = "a boolean";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => { Some(*b) }
+            _ => { None }
+        }
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => { Some(s.clone()) }
+            _ => { None }
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    const EXPECTED: &'static str = "a list";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(elements) => { elements.iter().map(T::from_value).collect() }
+            _ => { None }
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(num: f64) -> Self {
+        Value::Number(num)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(num: i64) -> Self {
+        Value::Number(num as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(elements: Vec<T>) -> Self {
+        Value::List(elements.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => { value.into() }
+            None => { Value::Nil }
+        }
+    }
+}
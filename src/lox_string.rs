@@ -0,0 +1,113 @@
+use crate::engine::{Error, Value};
+use crate::lox_native::{Arity, NativeRegistry};
+
+// Longest string `repeat` will build, so a huge count fails instead of aborting on allocation
+const MAX_REPEAT_BYTES: usize = 1 << 30;
+
+/// Add the string natives to `registry`. Positions and lengths count
+/// characters, not bytes, so multi-byte text is never split in the middle of
+/// a character.
+pub fn register(registry: &mut NativeRegistry) {
+    registry.register("substr", Arity::Range(2, 3), |args| {
+        let chars: Vec<char> = args.get::<String>(0)?.chars().collect();
+        let start = args.get::<i64>(1)?;
+        let length = chars.len() as i64;
+        let position = if start < 0 { start + length } else { start };
+        if position < 0 || position > length {
+            return Err(Error::runtime(format!("Index {start} is out of bounds for a string of length {length}.")));
+        }
+        // nil or no length takes the rest of the string, longer lengths stop at the end
+        let end = match args.optional::<i64>(2)? {
+            None => { length }
+            Some(taken) if taken < 0 => {
+                return Err(Error::runtime(format!("substr() length can't be negative, got {taken}.")));
+            }
+            Some(taken) => { position.saturating_add(taken).min(length) }
+        };
+        Ok(Value::String(chars[position as usize..end as usize].iter().collect()))
+    });
+    registry.register("index_of", Arity::Fixed(2), |args| {
+        let string = args.get::<String>(0)?;
+        let needle = args.get::<String>(1)?;
+        match string.find(&needle) {
+            Some(byte_index) => { Ok(Value::Number(string[..byte_index].chars().count() as f64)) }
+            None => { Ok(Value::Number(-1.0)) }
+        }
+    });
+    registry.register("split", Arity::Fixed(2), |args| {
+        let string = args.get::<String>(0)?;
+        let separator = args.get::<String>(1)?;
+        // An empty separator splits between every character
+        let parts: Vec<Value> = if separator.is_empty() {
+            string.chars().map(|c| Value::String(c.to_string())).collect()
+        } else {
+            string.split(&separator).map(Value::from).collect()
+        };
+        Ok(Value::List(parts))
+    });
+    registry.register("replace", Arity::Fixed(3), |args| {
+        let string = args.get::<String>(0)?;
+        let from = args.get::<String>(1)?;
+        let to = args.get::<String>(2)?;
+        if from.is_empty() {
+            return Err(Error::runtime("replace() can't replace an empty string.".to_string()));
+        }
+        Ok(Value::String(string.replace(&from, &to)))
+    });
+    registry.register("trim", Arity::Fixed(1), |args| {
+        Ok(Value::from(args.get::<String>(0)?.trim()))
+    });
+    registry.register("upper", Arity::Fixed(1), |args| {
+        Ok(Value::String(args.get::<String>(0)?.to_uppercase()))
+    });
+    registry.register("lower", Arity::Fixed(1), |args| {
+        Ok(Value::String(args.get::<String>(0)?.to_lowercase()))
+    });
+    registry.register("starts_with", Arity::Fixed(2), |args| {
+        Ok(Value::Bool(args.get::<String>(0)?.starts_with(&args.get::<String>(1)?)))
+    });
+    registry.register("ends_with", Arity::Fixed(2), |args| {
+        Ok(Value::Bool(args.get::<String>(0)?.ends_with(&args.get::<String>(1)?)))
+    });
+    registry.register("repeat", Arity::Fixed(2), |args| {
+        let string = args.get::<String>(0)?;
+        let count = args.get::<i64>(1)?;
+        if count < 0 {
+            return Err(Error::runtime(format!("repeat() count can't be negative, got {count}.")));
+        }
+        let length = string.len().checked_mul(count as usize).filter(|length| *length <= MAX_REPEAT_BYTES);
+        match length {
+            Some(_) => { Ok(Value::String(string.repeat(count as usize))) }
+            None => { Err(Error::runtime(format!("repeat() would build a string longer than {MAX_REPEAT_BYTES} bytes."))) }
+        }
+    });
+    registry.register("join", Arity::Fixed(2), |args| {
+        let mut parts = Vec::new();
+        for element in args.get::<Vec<Value>>(0)? {
+            match element {
+                Value::String(part) => { parts.push(part); }
+                _ => { return Err(Error::runtime("join() expects a list of strings.".to_string())); }
+            }
+        }
+        Ok(Value::String(parts.join(&args.get::<String>(1)?)))
+    });
+    registry.register("chars", Arity::Fixed(1), |args| {
+        let string = args.get::<String>(0)?;
+        Ok(Value::List(string.chars().map(|c| Value::String(c.to_string())).collect()))
+    });
+    registry.register("ord", Arity::Fixed(1), |args| {
+        let string = args.get::<String>(0)?;
+        let mut chars = string.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => { Ok(Value::Number(c as u32 as f64)) }
+            _ => { Err(Error::runtime(format!("ord() expects a single character, got \"{string}\"."))) }
+        }
+    });
+    registry.register("chr", Arity::Fixed(1), |args| {
+        let code = args.get::<i64>(0)?;
+        match u32::try_from(code).ok().and_then(char::from_u32) {
+            Some(c) => { Ok(Value::String(c.to_string())) }
+            None => { Err(Error::runtime(format!("chr() got {code}, which is not a valid code point."))) }
+        }
+    });
+}
@@ -1,28 +1,60 @@
-use std::{env, process};
-use std::io::{Read, Write};
+use std::str::FromStr;
+use std::{env, panic, process, thread};
 
-use crate::lox::Lox;
+use loxrs::{Backend, Limits, Lox};
 
-mod scanner;
-mod lox;
-mod ast;
-mod printer;
-mod token;
-mod parser;
-mod interpreter;
-mod environment;
-mod lox_callable;
+// Scripts run on a thread with a stack this big, so they can recurse deeply
+const STACK_SIZE: usize = 256 << 20;
 
 fn main(){
-    let args: Vec<String> = env::args().collect();
-    let mut interpreter = Lox::new();
-    if args.len() > 2{
-        println!("Usage: loxrs [script]");
-        process::exit(0);
-    } else if args.len()==2 {
-        interpreter.run_file(&args[1]);
-    } else {
-        interpreter.run_prompt();
+    let runner = thread::Builder::new().stack_size(STACK_SIZE).spawn(run).expect("Couldn't start the interpreter thread");
+    if let Err(panic) = runner.join() {
+        panic::resume_unwind(panic);
     }
 }
 
+fn run() {
+    let mut args = env::args().skip(1);
+
+    // Options for loxrs come before the script, everything after it is for the script
+    let mut backend = Backend::TreeWalker;
+    let mut sandboxed = false;
+    let mut limits = Limits::default();
+    let mut script = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--vm" => { backend = Backend::Bytecode; }
+            "--sandbox" => { sandboxed = true; }
+            "--max-steps" => { limits.max_steps = Some(count_arg(args.next())); }
+            "--max-depth" => { limits.max_call_depth = Some(count_arg(args.next())); }
+            option if option.starts_with("--") => { usage(); }
+            _ => {
+                script = Some(arg);
+                break;
+            }
+        }
+    }
+
+    let mut interpreter = Lox::with_backend(backend);
+    interpreter.set_stack_size(STACK_SIZE);
+    interpreter.set_sandboxed(sandboxed);
+    interpreter.set_limits(limits);
+    interpreter.set_script_args(args.collect());
+    match script {
+        Some(script) => { interpreter.run_file(&script); }
+        None => { interpreter.run_prompt(); }
+    }
+}
+
+/// The number following a limit option
+fn count_arg<T: FromStr>(arg: Option<String>) -> T {
+    match arg.map(|arg| arg.parse()) {
+        Some(Ok(count)) => { count }
+        _ => { usage() }
+    }
+}
+
+fn usage() -> ! {
+    println!("Usage: loxrs [--vm] [--sandbox] [--max-steps N] [--max-depth N] [script [args...]]");
+    process::exit(64);
+}
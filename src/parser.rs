@@ -1,11 +1,11 @@
 use crate::ast::{Expr, Stmt};
 use crate::interpreter::InterpreterError;
-use crate::lox::Lox;
 use crate::token::{LiteralValue, Token, TokenType};
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
@@ -13,55 +13,160 @@ impl Parser {
         Parser {
             tokens,
             current: 0,
+            errors: Vec::new(),
         }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParseError> {
+    /// Parse the whole token stream, collecting every syntax error instead of
+    /// stopping at the first one
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
         let mut statements: Vec<Stmt> = vec![];
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
+        }
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
         }
-        Ok(statements)
     }
 
-    fn declaration(&mut self) -> Result<Stmt, ParseError> {
-        if self.match_token(vec![TokenType::Fun]) {return self.function("function");}
-        if self.match_token(vec![TokenType::Var]) {
-            match self.var_declaration() {
-                Ok(stmt) => { Ok(stmt) }
-                Err(e) => {
-                    self.synchronize();
-                    return Err(e);
-                }
+    /// Parse a declaration, on a syntax error record it and skip ahead to the
+    /// start of the next statement
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.declaration_inner() {
+            Ok(stmt) => { Some(stmt) }
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize();
+                None
             }
-        } else {
-            return self.statement();
         }
     }
+
+    fn declaration_inner(&mut self) -> Result<Stmt, ParseError> {
+        if self.match_token(vec![TokenType::Class]) {return self.class_declaration();}
+        if self.match_token(vec![TokenType::Export]) { return self.export_declaration(); }
+        // `fun (` starts a lambda, which is left to the expression statement
+        if self.check(TokenType::Fun) && !self.check_next(TokenType::LeftParen) {
+            self.advance();
+            return self.function("function");
+        }
+        if self.match_token(vec![TokenType::Var]) {
+            return self.var_declaration();
+        }
+        self.statement()
+    }
     
+    fn export_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let keyword: Token = self.previous();
+        let declaration: Stmt = if self.match_token(vec![TokenType::Class]) {
+            self.class_declaration()?
+        } else if self.match_token(vec![TokenType::Fun]) {
+            self.function("function")?
+        } else if self.match_token(vec![TokenType::Var]) {
+            self.var_declaration()?
+        } else {
+            return Err(Parser::error(self.peek(), "Expect 'fun', 'var' or 'class' after 'export'."));
+        };
+        Ok(Stmt::new_export(keyword, declaration))
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name: Token = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let mut superclass: Option<Expr> = None;
+        if self.match_token(vec![TokenType::Less]) {
+            self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            superclass = Some(Expr::new_variable(self.previous()));
+        }
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods: Vec<Stmt> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::new_class(name, superclass, methods))
+    }
+
     fn function(&mut self, kind: &str)->Result<Stmt, ParseError>{
-        let name:Token = self.consume(TokenType::Identifier, &format!("Expect {kind} name."));
-        self.consume(TokenType::LeftParen, &format!("Expect '(' after {kind} name."));
+        let name:Token = self.consume(TokenType::Identifier, &format!("Expect {kind} name."))?;
+        self.consume(TokenType::LeftParen, &format!("Expect '(' after {kind} name."))?;
+        let parameters: Vec<Token> = self.parameters()?;
+        
+        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {kind} body."))?;
+        let body: Vec<Stmt> = self.block()?;
+        return Ok(Stmt::new_function(name, parameters, body))
+    }
+
+    /// Parameter names up to and including the closing parenthesis
+    fn parameters(&mut self) -> Result<Vec<Token>, ParseError> {
         let mut  parameters: Vec<Token> = Vec::new();
         if !self.check(TokenType::RightParen) {
             loop {
-                if parameters.len()>255 {
-                    return Err(ParseError{
-                        token: self.peek(),
-                        message: "Can't have more than 255 parameters.".to_string()
-                    })
+                if parameters.len()>=255 {
+                    let error = Parser::error(self.peek(), "Can't have more than 255 parameters.");
+                    self.errors.push(error);
                 }
                 
-                parameters.push(self.consume(TokenType::Identifier, "Expect parameter name."));
+                parameters.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
                 
                 if !self.match_token(vec![TokenType::Comma]){break;}
             }
         }
-        self.consume(TokenType::RightParen, "Expect ')' after parameters.");
-        
-        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {kind} body."));
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        Ok(parameters)
+    }
+
+    /// `fun (params) { body }` used as a value
+    fn lambda(&mut self) -> Result<Expr, ParseError> {
+        let keyword: Token = self.previous();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'fun'.")?;
+        let parameters: Vec<Token> = self.parameters()?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before lambda body.")?;
         let body: Vec<Stmt> = self.block()?;
-        return Ok(Stmt::new_function(name, parameters, body))
+        let span = keyword.span.to(self.previous().span);
+        Ok(Expr::new_lambda(Stmt::new_function(Parser::lambda_name(&keyword), parameters, body), span))
+    }
+
+    /// `(params) => expression`, the body returns the value of the expression
+    fn arrow_function(&mut self) -> Result<Expr, ParseError> {
+        let left_paren: Token = self.advance();
+        let parameters: Vec<Token> = self.parameters()?;
+        let arrow: Token = self.consume(TokenType::Arrow, "Expect '=>' after parameters.")?;
+        let value: Expr = self.expression()?;
+        let span = left_paren.span.to(value.span());
+        let body = vec![Stmt::new_return(arrow, Some(value))];
+        Ok(Expr::new_lambda(Stmt::new_function(Parser::lambda_name(&left_paren), parameters, body), span))
+    }
+
+    fn lambda_name(start: &Token) -> Token {
+        Token::spanning(TokenType::Identifier, "lambda".to_string(), LiteralValue::None, start.span)
+    }
+
+    /// Whether the parenthesis at the current token opens the parameter list
+    /// of an arrow function rather than a grouping
+    fn arrow_ahead(&self) -> bool {
+        let token_type = |i: usize| self.tokens.get(i).map(|token| token.token_type);
+        let mut i = self.current + 1;
+        if token_type(i) == Some(TokenType::RightParen) {
+            return token_type(i + 1) == Some(TokenType::Arrow);
+        }
+        loop {
+            if token_type(i) != Some(TokenType::Identifier) {
+                return false;
+            }
+            match token_type(i + 1) {
+                Some(TokenType::Comma) => { i += 2; }
+                Some(TokenType::RightParen) => { return token_type(i + 2) == Some(TokenType::Arrow); }
+                _ => { return false; }
+            }
+        }
     }
 
     fn expression(&mut self) -> Result<Expr, ParseError> {
@@ -69,10 +174,15 @@ impl Parser {
     }
 
     fn statement(&mut self) -> Result<Stmt, ParseError> {
+        if self.match_token(vec![TokenType::Break]) { return self.loop_jump_statement(); }
+        if self.match_token(vec![TokenType::Continue]) { return self.loop_jump_statement(); }
         if self.match_token(vec![TokenType::For]) { return self.for_statement();}
         if self.match_token(vec![TokenType::If]) { return self.if_statement(); }
+        if self.match_token(vec![TokenType::Import]) { return self.import_statement(); }
         if self.match_token(vec![TokenType::Print]) { return self.print_statement(); }
         if self.match_token(vec![TokenType::Return]) {return self.return_statement(); }
+        if self.match_token(vec![TokenType::Throw]) { return self.throw_statement(); }
+        if self.match_token(vec![TokenType::Try]) { return self.try_statement(); }
         if self.match_token(vec![TokenType::While]) {return self.while_statement();}
         if self.match_token(vec![TokenType::LeftBrace]) {
             return Ok(Stmt::new_block(self.block()?));
@@ -89,10 +199,21 @@ impl Parser {
             let value: Expr = self.assignment()?;
 
             match expr {
-                Expr::Variable { name } => {
+                Expr::Variable { name, .. } => {
                     return Ok(Expr::new_assign(name, value));
                 }
-                _ => { return Err(ParseError { token: equals, message: "Invalid assignment target.".to_string() }) }
+                Expr::Get { object, name } => {
+                    return Ok(Expr::new_set(*object, name, value));
+                }
+                Expr::Index { object, bracket, index } => {
+                    return Ok(Expr::new_set_index(*object, bracket, *index, value));
+                }
+                _ => {
+                    // Report the bad target but keep parsing, the parser isn't confused
+                    let error = Parser::error(equals, "Invalid assignment target.");
+                    self.errors.push(error);
+                    return Ok(expr);
+                }
             }
         }
         Ok(expr)
@@ -105,24 +226,80 @@ impl Parser {
             value = Some(self.expression()?);
         }
         
-        self.consume(TokenType::SemiColon, "Expect ';' after return value.");
+        self.consume(TokenType::SemiColon, "Expect ';' after return value.")?;
         match value {
             None => {Ok(Stmt::new_return(keyword, None))}
             Some(expr) => {Ok(Stmt::new_return(keyword, Some(expr)))}
         }
     }
     
+    /// `break` or `continue`, the resolver checks that they are inside a loop
+    fn loop_jump_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword: Token = self.previous();
+        self.consume(TokenType::SemiColon, &format!("Expect ';' after '{}'.", keyword.lexeme))?;
+        match keyword.token_type {
+            TokenType::Break => { Ok(Stmt::new_break(keyword)) }
+            _ => { Ok(Stmt::new_continue(keyword)) }
+        }
+    }
+
+    /// `import "path";` or `import "path" as name;`, `as` is only special here
+    fn import_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword: Token = self.previous();
+        let path: Token = self.consume(TokenType::StringToken, "Expect module path after 'import'.")?;
+        let mut alias: Option<Token> = None;
+        if self.check(TokenType::Identifier) && self.peek().lexeme == "as" {
+            self.advance();
+            alias = Some(self.consume(TokenType::Identifier, "Expect module name after 'as'.")?);
+        }
+        self.consume(TokenType::SemiColon, "Expect ';' after import.")?;
+        Ok(Stmt::new_import(keyword, path, alias))
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword: Token = self.previous();
+        let value: Expr = self.expression()?;
+        self.consume(TokenType::SemiColon, "Expect ';' after thrown value.")?;
+        Ok(Stmt::new_throw(keyword, value))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword: Token = self.previous();
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body: Vec<Stmt> = self.block()?;
+
+        let mut catch: Option<(Token, Vec<Stmt>)> = None;
+        if self.match_token(vec![TokenType::Catch]) {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+            let name: Token = self.consume(TokenType::Identifier, "Expect error variable name.")?;
+            self.consume(TokenType::RightParen, "Expect ')' after error variable.")?;
+            self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+            catch = Some((name, self.block()?));
+        }
+
+        let mut finally: Option<Vec<Stmt>> = None;
+        if self.match_token(vec![TokenType::Finally]) {
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            finally = Some(self.block()?);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(Parser::error(self.peek(), "Expect 'catch' or 'finally' after try block."));
+        }
+        Ok(Stmt::new_try(keyword, body, catch, finally))
+    }
+
     fn while_statement(&mut self)->Result<Stmt, ParseError>{
-        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.");
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition:Expr = self.expression()?;
-        self.consume(TokenType::RightParen, "Expect ')' after condition. ");
+        self.consume(TokenType::RightParen, "Expect ')' after condition. ")?;
         let body: Stmt = self.statement()?;
         
-        return Ok(Stmt::new_while(condition, body))
+        return Ok(Stmt::new_while(condition, body, None))
     }
     
     fn for_statement(&mut self)-> Result<Stmt, ParseError>{
-        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
         
         let mut initializer: Option<Stmt> = None;
         if self.match_token(vec![TokenType::Var]){
@@ -136,30 +313,23 @@ impl Parser {
         if !self.check(TokenType::SemiColon){
             condition = Some(self.expression()?);
         }
-        self.consume(TokenType::SemiColon, "Expect ';' after loop condition.");
+        let condition_end: Token = self.consume(TokenType::SemiColon, "Expect ';' after loop condition.")?;
         
         let mut increment:Option<Expr> = None;
         if !self.check(TokenType::RightParen) {
             increment = Some(self.expression()?);
         }
-        self.consume(TokenType::RightParen, "Expect ')' after for clauses.");
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
         
         let mut body: Stmt = self.statement()?;
         
-        match increment {
-            None => {}
-            Some(inc) => {
-                body = Stmt::new_block(vec![body.clone(), Stmt::new_expression(inc)]);
-            }
-        }
-        
         match condition {
             None => {
-                let cond= Expr::new_literal(LiteralValue::True);
-                body = Stmt::new_while(cond, body.clone());
+                let cond= Expr::new_literal(LiteralValue::True, condition_end.span);
+                body = Stmt::new_while(cond, body.clone(), increment);
             }
             Some(cond) => {
-                body = Stmt::new_while(cond, body.clone());
+                body = Stmt::new_while(cond, body.clone(), increment);
             }
         }
         
@@ -197,9 +367,9 @@ impl Parser {
     }
 
     fn if_statement(&mut self) -> Result<Stmt, ParseError> {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'if'");
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'")?;
         let condition: Expr = self.expression()?;
-        self.consume(TokenType::RightParen, "Expect ')' after if condition.");
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
 
         let then_branch = self.statement();
         let mut else_branch: Option<Stmt> = None;
@@ -210,30 +380,30 @@ impl Parser {
         Ok(Stmt::new_if(condition, then_branch?, else_branch))
     }
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
-        let name: Token = self.consume(TokenType::Identifier, "Expect variable name.");
+        let name: Token = self.consume(TokenType::Identifier, "Expect variable name.")?;
 
         let mut initializer: Expr;
 
         if self.match_token(vec![TokenType::Equal]) {
             initializer = self.expression()?;
-            self.consume(TokenType::SemiColon, "Expect ';' after variable declaration.");
+            self.consume(TokenType::SemiColon, "Expect ';' after variable declaration.")?;
 
             return Ok(Stmt::new_variable_initialized(name, initializer));
         }
 
-        self.consume(TokenType::SemiColon, "Expect ';' after variable declaration.");
+        self.consume(TokenType::SemiColon, "Expect ';' after variable declaration.")?;
         Ok(Stmt::new_variable_uninitialized(name))
     }
 
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
         let value: Expr = self.expression()?;
-        self.consume(TokenType::SemiColon, "Expect ';' after value.");
+        self.consume(TokenType::SemiColon, "Expect ';' after value.")?;
         return Ok(Stmt::new_print(value));
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
         let expr: Expr = self.expression()?;
-        self.consume(TokenType::SemiColon, "Expect ';' after expression.");
+        self.consume(TokenType::SemiColon, "Expect ';' after expression.")?;
         Ok(Stmt::new_expression(expr))
     }
 
@@ -298,6 +468,13 @@ impl Parser {
         loop {
             if self.match_token(vec![TokenType::LeftParen]){
                 expr = self.finish_call(expr)?;
+            } else if self.match_token(vec![TokenType::Dot]) {
+                let name: Token = self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Expr::new_get(expr, name);
+            } else if self.match_token(vec![TokenType::LeftBracket]) {
+                let index: Expr = self.expression()?;
+                let bracket: Token = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::new_index(expr, bracket, index);
             } else {
                 break;
             }
@@ -310,8 +487,8 @@ impl Parser {
         if !self.check(TokenType::RightParen) {
             loop {
                 if arguments.len() >= 255 {
-                    return Err(ParseError{token: self.peek(), 
-                        message: "Can't have more than 255 arguments.".to_string()});
+                    let error = Parser::error(self.peek(), "Can't have more than 255 arguments.");
+                    self.errors.push(error);
                 }
                 arguments.push(self.expression()?);
                 if !self.match_token(vec![TokenType::Comma]){
@@ -320,40 +497,99 @@ impl Parser {
             }
         }
         
-        let paren: Token = self.consume(TokenType::RightParen, "Expect ')' after arguments.");
+        let paren: Token = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
         
         return Ok(Expr::new_call(callee, paren, arguments))
     }
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
-        if self.match_token(vec![TokenType::False]) { return Ok(Expr::new_literal(LiteralValue::False)); }
-        if self.match_token(vec![TokenType::True]) { return Ok(Expr::new_literal(LiteralValue::True)); }
-        if self.match_token(vec![TokenType::Nil]) { return Ok(Expr::new_literal(LiteralValue::None)); }
+        if self.match_token(vec![TokenType::False]) { return Ok(Expr::new_literal(LiteralValue::False, self.previous().span)); }
+        if self.match_token(vec![TokenType::True]) { return Ok(Expr::new_literal(LiteralValue::True, self.previous().span)); }
+        if self.match_token(vec![TokenType::Nil]) { return Ok(Expr::new_literal(LiteralValue::None, self.previous().span)); }
 
         if self.match_token(vec![TokenType::Number, TokenType::StringToken]) {
-            return Ok(Expr::new_literal(self.previous().literal));
+            let token: Token = self.previous();
+            return Ok(Expr::new_literal(token.literal, token.span));
         }
 
+        if self.match_token(vec![TokenType::Super]) {
+            let keyword: Token = self.previous();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method: Token = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(Expr::new_super(keyword, method));
+        }
+        if self.match_token(vec![TokenType::This]) { return Ok(Expr::new_this(self.previous())); }
+
         if self.match_token(vec![TokenType::Identifier]) {
             return Ok(Expr::new_variable(self.previous()));
         }
 
+        if self.match_token(vec![TokenType::Fun]) {
+            return self.lambda();
+        }
+
+        if self.check(TokenType::LeftParen) && self.arrow_ahead() {
+            return self.arrow_function();
+        }
+
+        if self.match_token(vec![TokenType::LeftBracket]) {
+            return self.list();
+        }
+
+        // A brace starting a statement is always a block, see `statement`, so
+        // here it can only open a map
+        if self.match_token(vec![TokenType::LeftBrace]) {
+            return self.map();
+        }
+
         if self.match_token(vec![TokenType::LeftParen]) {
+            let left_paren: Token = self.previous();
             let expr: Expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expect ')' after expression.");
-            return Ok(Expr::new_grouing(expr));
+            let right_paren: Token = self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            return Ok(Expr::new_grouing(expr, left_paren.span.to(right_paren.span)));
         }
 
         Err(Parser::error(self.peek(), "Expect expression."))
     }
 
+    fn list(&mut self) -> Result<Expr, ParseError> {
+        let left_bracket: Token = self.previous();
+        let mut elements: Vec<Expr> = Vec::new();
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                elements.push(self.expression()?);
+                if !self.match_token(vec![TokenType::Comma]) { break; }
+            }
+        }
+        let right_bracket: Token = self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+        Ok(Expr::new_list(elements, left_bracket.span.to(right_bracket.span)))
+    }
+
+    fn map(&mut self) -> Result<Expr, ParseError> {
+        let left_brace: Token = self.previous();
+        let mut entries: Vec<(Expr, Expr)> = Vec::new();
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                let key: Expr = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                let value: Expr = self.expression()?;
+                entries.push((key, value));
+                if !self.match_token(vec![TokenType::Comma]) { break; }
+            }
+        }
+        let right_brace: Token = self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+        Ok(Expr::new_map(entries, left_brace.span.to(right_brace.span)))
+    }
+
     fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut statements: Vec<Stmt> = Vec::new();
 
         while (!self.check(TokenType::RightBrace) && !self.is_at_end()) {
-            statements.push(self.declaration()?);
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
         }
-        self.consume(TokenType::RightBrace, "Expect '}' after block.");
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
         return Ok(statements);
     }
 
@@ -372,6 +608,13 @@ impl Parser {
         return self.peek().token_type == token_type;
     }
 
+    fn check_next(&self, token_type: TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => { token.token_type == token_type }
+            None => { false }
+        }
+    }
+
     fn advance(&mut self) -> Token {
         if !self.is_at_end() { self.current += 1 };
         return self.previous();
@@ -389,15 +632,14 @@ impl Parser {
         return self.tokens[self.current - 1].clone();
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Token {
-        if self.check(token_type) { return self.advance(); }
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, ParseError> {
+        if self.check(token_type) { return Ok(self.advance()); }
 
-        panic!("{}: {}", self.peek(), message)
+        Err(Parser::error(self.peek(), message))
     }
 
     fn error(token: Token, message: &str) -> ParseError {
-        Lox::error_token(token.clone(), message);
-        return ParseError { token, message: message.to_string() };
+        ParseError { token, message: message.to_string() }
     }
 
     fn synchronize(&mut self) {
@@ -416,6 +658,12 @@ impl Parser {
                 TokenType::While => { return }
                 TokenType::Print => { return }
                 TokenType::Return => { return }
+                TokenType::Break => { return }
+                TokenType::Continue => { return }
+                TokenType::Throw => { return }
+                TokenType::Try => { return }
+                TokenType::Import => { return }
+                TokenType::Export => { return }
                 _ => {}
             }
             self.advance();
@@ -424,6 +672,6 @@ impl Parser {
 }
 
 pub struct ParseError {
-    token: Token,
-    message: String,
+    pub token: Token,
+    pub message: String,
 }
\ No newline at end of file
@@ -15,12 +15,17 @@ impl Visitor<String> for AstPrinter {
             }
             Expr::Call { .. } => {format!("")}
             Expr::Get { .. } => {format!("")}
-            Expr::Grouping { expression } => {
+            Expr::Grouping { expression, .. } => {
                 self.parenthesize(String::from("group"), vec![expression])
             }
-            Expr::Literal { value } => { format!("{value}")}
+            Expr::Index { .. } => {format!("")}
+            Expr::Lambda { .. } => {format!("")}
+            Expr::List { .. } => {format!("")}
+            Expr::Literal { value, .. } => { format!("{value}")}
             Expr::Logical { .. } => {format!("")}
+            Expr::Map { .. } => {format!("")}
             Expr::Set { .. } => {format!("")}
+            Expr::SetIndex { .. } => {format!("")}
             Expr::Super { .. } => {format!("")}
             Expr::This { .. } => {format!("")}
             Expr::Unary { operator, right } => {
@@ -33,12 +38,18 @@ impl Visitor<String> for AstPrinter {
     fn visit_stmt(&mut self, stmt: &mut Stmt) -> String {
         match stmt{
             Stmt::Block { .. } => {format!("")}
+            Stmt::Break { .. } => {format!("")}
             Stmt::Class { .. } => {format!("")}
+            Stmt::Continue { .. } => {format!("")}
+            Stmt::Export { .. } => {format!("")}
             Stmt::Expression { .. } => {format!("")}
             Stmt::Function { .. } => {format!("")}
             Stmt::If { .. } => {format!("")}
+            Stmt::Import { .. } => {format!("")}
             Stmt::Print { .. } => {format!("")}
             Stmt::Return { .. } => {format!("")}
+            Stmt::Throw { .. } => {format!("")}
+            Stmt::Try { .. } => {format!("")}
             Stmt::Variable { .. } => {format!("")}
             Stmt::While { .. } => {format!("")}
         }
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, Stmt, Visitor};
+use crate::token::Token;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Static pass run between parsing and interpreting, records how many scopes
+/// separate each variable reference from its declaration
+pub struct Resolver {
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    // Loops enclosing the current statement within the current function
+    loop_depth: usize,
+    errors: Vec<ResolverError>,
+}
+
+pub struct ResolverError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl Visitor<()> for Resolver {
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Assign { name, value, depth } => {
+                self.resolve_expr(value);
+                *depth = self.resolve_local(name);
+            }
+            Expr::Binary { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call { callee, arguments, .. } => {
+                self.resolve_expr(callee);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Get { object, .. } => {
+                self.resolve_expr(object);
+            }
+            Expr::Grouping { expression, .. } => {
+                self.resolve_expr(expression);
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::Lambda { declaration, .. } => {
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Expr::List { elements, .. } => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Literal { .. } => {}
+            Expr::Logical { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::Set { object, value, .. } => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::SetIndex { object, index, value, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::Super { keyword, depth, .. } => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword.clone(), "Can't use 'super' outside of a class.");
+                } else if self.current_class != ClassType::Subclass {
+                    self.error(keyword.clone(), "Can't use 'super' in a class with no superclass.");
+                }
+                *depth = self.resolve_local(keyword);
+            }
+            Expr::This { keyword, depth } => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword.clone(), "Can't use 'this' outside of a class.");
+                    return;
+                }
+                *depth = self.resolve_local(keyword);
+            }
+            Expr::Unary { right, .. } => {
+                self.resolve_expr(right);
+            }
+            Expr::Variable { name, depth } => {
+                if let Some(scope) = self.scopes.last() {
+                    if scope.get(&name.lexeme) == Some(&false) {
+                        self.error(name.clone(), "Can't read local variable in its own initializer.");
+                    }
+                }
+                *depth = self.resolve_local(name);
+            }
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            Stmt::Break { keyword } => {
+                if self.loop_depth == 0 {
+                    self.error(keyword.clone(), "Can't use 'break' outside of a loop.");
+                }
+            }
+            Stmt::Class { name, superclass, methods } => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass_expr) = superclass {
+                    if let Expr::Variable { name: superclass_name, .. } = superclass_expr.as_ref() {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.error(superclass_name.clone(), "A class can't inherit from itself.");
+                        }
+                    }
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expr(superclass_expr);
+
+                    self.begin_scope();
+                    self.define_name("super");
+                }
+
+                self.begin_scope();
+                self.define_name("this");
+
+                for method in methods {
+                    let declaration = match method.as_ref() {
+                        Stmt::Function { name, .. } if name.lexeme == "init" => { FunctionType::Initializer }
+                        _ => { FunctionType::Method }
+                    };
+                    self.resolve_function(method, declaration);
+                }
+
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::Continue { keyword } => {
+                if self.loop_depth == 0 {
+                    self.error(keyword.clone(), "Can't use 'continue' outside of a loop.");
+                }
+            }
+            Stmt::Export { keyword, declaration } => {
+                if !self.scopes.is_empty() {
+                    self.error(keyword.clone(), "Can only export from the top level of a file.");
+                }
+                self.resolve_stmt(declaration);
+            }
+            Stmt::Expression { expression } => {
+                self.resolve_expr(expression);
+            }
+            Stmt::Function { name, .. } => {
+                let name = name.clone();
+                self.declare(&name);
+                self.define(&name);
+                self.resolve_function(stmt, FunctionType::Function);
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::Import { keyword, alias, .. } => {
+                if !self.scopes.is_empty() {
+                    self.error(keyword.clone(), "Imports must be at the top level of a file.");
+                }
+                if let Some(alias) = alias {
+                    self.declare(alias);
+                    self.define(alias);
+                }
+            }
+            Stmt::Print { expression } => {
+                self.resolve_expr(expression);
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    self.error(keyword.clone(), "Can't return from top-level code.");
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword.clone(), "Can't return a value from an initializer.");
+                    }
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Throw { value, .. } => {
+                self.resolve_expr(value);
+            }
+            Stmt::Try { body, catch, finally, .. } => {
+                self.begin_scope();
+                self.resolve_statements(body);
+                self.end_scope();
+                if let Some((name, catch_body)) = catch {
+                    // The error variable shares a scope with the handler's statements
+                    self.begin_scope();
+                    self.declare(name);
+                    self.define(name);
+                    self.resolve_statements(catch_body);
+                    self.end_scope();
+                }
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.resolve_statements(finally);
+                    self.end_scope();
+                }
+            }
+            Stmt::Variable { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::While { condition, body, increment } => {
+                self.resolve_expr(condition);
+                self.loop_depth += 1;
+                self.resolve_stmt(body);
+                self.loop_depth -= 1;
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+            }
+        }
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            loop_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &mut Vec<Stmt>) -> Result<(), Vec<ResolverError>> {
+        for statement in statements.iter_mut() {
+            self.resolve_stmt(statement);
+        }
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &mut Vec<Box<Stmt>>) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut Stmt) {
+        self.visit_stmt(stmt)
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) {
+        self.visit_expr(expr)
+    }
+
+    fn resolve_function(&mut self, function: &mut Stmt, function_type: FunctionType) {
+        if let Stmt::Function { params, body, .. } = function {
+            let enclosing_function = self.current_function;
+            self.current_function = function_type;
+            // A loop around the declaration can't be broken out of from inside the body
+            let enclosing_loops = std::mem::replace(&mut self.loop_depth, 0);
+
+            self.begin_scope();
+            for param in params.iter() {
+                self.declare(param);
+                self.define(param);
+            }
+            self.resolve_statements(body);
+            self.end_scope();
+
+            self.current_function = enclosing_function;
+            self.loop_depth = enclosing_loops;
+        }
+    }
+
+    /// Number of scopes between the innermost scope and the one declaring
+    /// the name, or None if the variable is assumed to be global
+    fn resolve_local(&self, name: &Token) -> Option<usize> {
+        for (i, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let already_declared = match self.scopes.last_mut() {
+            None => { return; }
+            Some(scope) => {
+                scope.insert(name.lexeme.clone(), false).is_some()
+            }
+        };
+        if already_declared {
+            self.error(name.clone(), "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        self.define_name(&name.lexeme);
+    }
+
+    fn define_name(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+
+    fn error(&mut self, token: Token, message: &str) {
+        self.errors.push(ResolverError { token, message: message.to_string() });
+    }
+}
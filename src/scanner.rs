@@ -1,26 +1,50 @@
 use std::collections::VecDeque;
-use crate::lox::Lox;
 use std::collections::HashMap;
-use crate::token::{LiteralValue, Token, TokenType};
+use crate::token::{LiteralValue, Span, Token, TokenType};
 
 pub struct Lexer{
     source: Vec<char>,
     pub tokens: VecDeque<Token>,
+    pub errors: Vec<ScanError>,
     at_end: bool,
     start:usize,
     current:usize,
     line:i32,
+    // Byte offsets of the token start and the current character
+    start_offset:usize,
+    offset:usize,
+    // Position of the token start, and the index of the first character of the current line
+    start_line:i32,
+    start_column:usize,
+    line_start:usize,
+    // Copied into every span, see `Span::source`
+    source_id:u32,
     keywords:HashMap<String, TokenType>,
 }
 
+pub struct ScanError {
+    pub span: Span,
+    pub message: String,
+}
+
 impl Lexer{
     pub fn scan_tokens(&mut self)->&VecDeque<Token>{
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_offset = self.offset;
+            self.start_line = self.line;
+            self.start_column = self.current - self.line_start + 1;
             self.scan_token();
         }
 
-        self.tokens.push_back(Token::new(TokenType::Eof, String::new(), LiteralValue::None, self.line));
+        let eof_span = Span {
+            offset: self.offset,
+            length: 0,
+            line: self.line,
+            column: self.current - self.line_start + 1,
+            source: self.source_id,
+        };
+        self.tokens.push_back(Token::spanning(TokenType::Eof, String::new(), LiteralValue::None, eof_span));
         return &self.tokens;
     }
 
@@ -32,6 +56,9 @@ impl Lexer{
             ')'=>self.add_token(TokenType::RightParen, LiteralValue::None),
             '{'=>self.add_token(TokenType::LeftBrace, LiteralValue::None),
             '}'=>self.add_token(TokenType::RightBrace, LiteralValue::None),
+            '['=>self.add_token(TokenType::LeftBracket, LiteralValue::None),
+            ']'=>self.add_token(TokenType::RightBracket, LiteralValue::None),
+            ':'=>self.add_token(TokenType::Colon, LiteralValue::None),
             ','=>self.add_token(TokenType::Comma, LiteralValue::None),
             '.'=>self.add_token(TokenType::Dot, LiteralValue::None),
             '-'=>self.add_token(TokenType::Minus, LiteralValue::None),
@@ -47,9 +74,10 @@ impl Lexer{
                 }
                 },
             '='=>{
-                let next_match = self.check_next('=');
-                if next_match {
+                if self.check_next('=') {
                     self.add_token(TokenType::EqualEqual, LiteralValue::None);
+                } else if self.check_next('>') {
+                    self.add_token(TokenType::Arrow, LiteralValue::None);
                 } else {
                     self.add_token(TokenType::Equal, LiteralValue::None);
                 }
@@ -82,30 +110,40 @@ impl Lexer{
             ' '=>{},
             '\r'=>{},
             '\t'=>{},
-            '\n'=>{self.line+=1},
+            '\n'=>{self.new_line()},
             '"'=>self.read_string(),
             '\0'=>{},
             '0'..='9'=>{self.read_number()},
             'a'..='z'|'A'..='Z'|'_'=>{self.read_identifier()},
-            _=> Lox::error(self.line, "Unexpected character.")
+            _=> self.error("Unexpected character.")
         }
     }
 
     fn advance(&mut self)->char{
         let char_at_current = self.source[self.current];
         self.current+=1;
+        self.offset+=char_at_current.len_utf8();
         char_at_current
     }
 
+    fn new_line(&mut self){
+        self.line+=1;
+        self.line_start = self.current;
+    }
+
+    fn error(&mut self, message: &str){
+        let span = self.current_span();
+        self.errors.push(ScanError { span, message: message.to_string() });
+    }
+
     fn read_string(&mut self){
         while self.peek()!='"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.advance() == '\n' {
+                self.new_line();
             }
-            self.advance();
         }
         if self.is_at_end(){
-            Lox::error(self.line, "Unterminated string.");
+            self.error("Unterminated string.");
             return ;
         }
         self.advance();
@@ -173,7 +211,7 @@ impl Lexer{
     fn check_next(&mut self, expected:char)->bool{
         if self.is_at_end() { return false};
         if self.source[self.current]!=expected {return false};
-        self.current+=1;
+        self.advance();
         return true;
     }
 
@@ -184,38 +222,80 @@ impl Lexer{
         return self.source[self.current];
     }
 
+    fn current_span(&self)->Span{
+        Span {
+            offset: self.start_offset,
+            length: self.offset - self.start_offset,
+            line: self.start_line,
+            column: self.start_column,
+            source: self.source_id,
+        }
+    }
+
     fn add_token(&mut self, token_type: TokenType, literal: LiteralValue){
         let text: String = self.source[self.start..self.current].iter().collect();
-        self.tokens.push_back(Token::new(token_type, text, literal, self.line));
+        let span = self.current_span();
+        self.tokens.push_back(Token::spanning(token_type, text, literal, span));
     }
 
     fn is_at_end(&self)->bool {
         self.current >= self.source.len()
     }
 
+    /// Scan source that follows on from `offset` bytes and `line - 1` lines
+    /// of earlier text, so spans from separate chunks don't overlap
+    pub fn continuing(source: String, offset: usize, line: i32)-> Lexer{
+        let mut lexer = Lexer::new(source);
+        lexer.offset = offset;
+        lexer.line = line;
+        lexer
+    }
+
+    /// Scan an imported file, its spans are tagged with the file's source id
+    pub fn for_module(source: String, source_id: u32) -> Lexer {
+        let mut lexer = Lexer::new(source);
+        lexer.source_id = source_id;
+        lexer
+    }
+
     pub fn new(source: String)-> Lexer{
         Lexer{
             source:source.chars().collect(),
             tokens: VecDeque::new(),
+            errors: Vec::new(),
             at_end:false,
             start:0,
             current:0,
             line:1,
+            start_offset:0,
+            offset:0,
+            start_line:1,
+            start_column:1,
+            line_start:0,
+            source_id:0,
             keywords: HashMap::from([
                 (String::from("and"), TokenType::And),
+                (String::from("break"), TokenType::Break),
+                (String::from("catch"), TokenType::Catch),
                 (String::from("class"), TokenType::Class),
+                (String::from("continue"), TokenType::Continue),
                 (String::from("else"), TokenType::Else),
+                (String::from("export"), TokenType::Export),
                 (String::from("false"), TokenType::False),
                 (String::from("for"), TokenType::For),
+                (String::from("finally"), TokenType::Finally),
                 (String::from("fun"), TokenType::Fun),
                 (String::from("if"), TokenType::If),
+                (String::from("import"), TokenType::Import),
                 (String::from("nil"), TokenType::Nil),
                 (String::from("or"), TokenType::Or),
                 (String::from("print"), TokenType::Print),
                 (String::from("return"), TokenType::Return),
                 (String::from("super"), TokenType::Super),
                 (String::from("this"), TokenType::This),
+                (String::from("throw"), TokenType::Throw),
                 (String::from("true"), TokenType::True),
+                (String::from("try"), TokenType::Try),
                 (String::from("var"), TokenType::Var),
                 (String::from("while"), TokenType::While),
             ])
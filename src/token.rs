@@ -5,10 +5,12 @@ use std::fmt;
 use std::fmt::Formatter;
 use std::ops::{Add, BitAnd, BitOr, Div, Mul, Neg, Not, Sub};
 use std::rc::Rc;
-use crate::environment::Environment;
+use crate::gc::GcRef;
 use crate::ast::Stmt;
 use crate::lox::Lox;
 use crate::lox_callable::LoxCallable;
+use crate::lox_class::{LoxClass, LoxInstance};
+use crate::lox_host::{HostMethod, HostRef};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValue {
@@ -18,29 +20,47 @@ pub enum LiteralValue {
     StringValue(String),
     NumValue(f64),
     IdentifierValue(String),
-    Function(FunctionEnum)
+    Function(FunctionEnum),
+    Class(Rc<LoxClass>),
+    Instance(LoxInstance),
+    List(GcRef),
+    Map(GcRef),
+    /// An object of a Rust type implementing `HostObject`
+    Host(HostRef),
 }
 
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum FunctionEnum {
-    Native(NativeFunctions),
-    User(UserDefinedFunction)
-}
-
-#[derive(Clone, Debug, PartialEq)]
-pub enum NativeFunctions {
-    Clock
+    /// Defined in Rust through the interpreter's `NativeRegistry`, by its id there
+    Native(usize),
+    User(UserDefinedFunction),
+    Host(Rc<HostMethod>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct UserDefinedFunction {
-    pub closure: Rc<RefCell<Environment>>,
+    pub closure: GcRef,
     pub declaration: Rc<RefCell<Stmt>>, 
+    pub is_initializer: bool,
 }
 
 
 
+impl fmt::Display for FunctionEnum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FunctionEnum::Native(_) => write!(f, "<native fn>"),
+            FunctionEnum::Host(method) => write!(f, "<method {}>", method.name),
+            FunctionEnum::User(fun) => {
+                match &*fun.declaration.borrow() {
+                    Stmt::Function { name, .. } => write!(f, "<fn {}>", name.lexeme),
+                    _ => write!(f, "<fn>"),
+                }
+            }
+        }
+    }
+}
 
 pub struct TokenError; // Error type for token errors, currently empty but can be extended later
 
@@ -53,7 +73,13 @@ impl fmt::Display for LiteralValue {
             LiteralValue::StringValue(s) => write!(f, "{s}"),
             LiteralValue::NumValue(x) => write!(f, "{x}"),
             LiteralValue::IdentifierValue(s) => write!(f, "{s}"),
-            LiteralValue::Function(fun)=> write!(f, "{:?}", fun)
+            LiteralValue::Function(fun)=> write!(f, "{}", fun),
+            LiteralValue::Class(class) => write!(f, "{}", class.name),
+            LiteralValue::Instance(instance) => write!(f, "{} instance", instance.class.name),
+            // Elements live on the interpreter heap, see Interpreter::stringify
+            LiteralValue::List(_) => write!(f, "<list>"),
+            LiteralValue::Map(_) => write!(f, "<map>"),
+            LiteralValue::Host(object) => write!(f, "{object}"),
         }
     }
 }
@@ -67,9 +93,14 @@ impl Neg for LiteralValue {
             LiteralValue::True => { Err(TokenError) }
             LiteralValue::False => { Err(TokenError) }
             LiteralValue::StringValue(_) => { Err(TokenError) }
-            LiteralValue::NumValue(n) => { Ok(LiteralValue::NumValue(n)) }
+            LiteralValue::NumValue(n) => { Ok(LiteralValue::NumValue(-n)) }
             LiteralValue::IdentifierValue(_) => { Err(TokenError) }
             LiteralValue::Function(_) => {Err(TokenError)}
+            LiteralValue::Instance(_) => {Err(TokenError)}
+            LiteralValue::Host(_) => {Err(TokenError)}
+            LiteralValue::Class(_) => {Err(TokenError)}
+            LiteralValue::List(_) => {Err(TokenError)}
+            LiteralValue::Map(_) => {Err(TokenError)}
         }
     }
 }
@@ -98,6 +129,11 @@ impl Not for LiteralValue {
             }
             LiteralValue::IdentifierValue(_)=>{Err(TokenError)}
             LiteralValue::Function(_) => {Err(TokenError)}
+            LiteralValue::Instance(_) => {Err(TokenError)}
+            LiteralValue::Host(_) => {Err(TokenError)}
+            LiteralValue::Class(_) => {Err(TokenError)}
+            LiteralValue::List(_) => {Err(TokenError)}
+            LiteralValue::Map(_) => {Err(TokenError)}
         }
     }
 }
@@ -123,6 +159,11 @@ impl Add for LiteralValue {
                     LiteralValue::NumValue(_) => {Err(TokenError)}
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::NumValue(lhs_num) => {
@@ -136,10 +177,20 @@ impl Add for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::IdentifierValue(_) => {Err(TokenError)}
             LiteralValue::Function(_) => {Err(TokenError)}
+            LiteralValue::Instance(_) => {Err(TokenError)}
+            LiteralValue::Host(_) => {Err(TokenError)}
+            LiteralValue::Class(_) => {Err(TokenError)}
+            LiteralValue::List(_) => {Err(TokenError)}
+            LiteralValue::Map(_) => {Err(TokenError)}
         }
 
     }
@@ -164,10 +215,20 @@ impl Sub for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::IdentifierValue(_) => {Err(TokenError)}
             LiteralValue::Function(_) => {Err(TokenError)}
+            LiteralValue::Instance(_) => {Err(TokenError)}
+            LiteralValue::Host(_) => {Err(TokenError)}
+            LiteralValue::Class(_) => {Err(TokenError)}
+            LiteralValue::List(_) => {Err(TokenError)}
+            LiteralValue::Map(_) => {Err(TokenError)}
         }
     }
 }
@@ -191,6 +252,11 @@ impl Mul for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::NumValue(lhs_num) => {
@@ -204,10 +270,20 @@ impl Mul for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::IdentifierValue(_) => {Err(TokenError)}
             LiteralValue::Function(_) => {Err(TokenError)}
+            LiteralValue::Instance(_) => {Err(TokenError)}
+            LiteralValue::Host(_) => {Err(TokenError)}
+            LiteralValue::Class(_) => {Err(TokenError)}
+            LiteralValue::List(_) => {Err(TokenError)}
+            LiteralValue::Map(_) => {Err(TokenError)}
         }
     }
 }
@@ -232,10 +308,20 @@ impl Div for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::IdentifierValue(_) => {Err(TokenError)}
             LiteralValue::Function(_) => {Err(TokenError)}
+            LiteralValue::Instance(_) => {Err(TokenError)}
+            LiteralValue::Host(_) => {Err(TokenError)}
+            LiteralValue::Class(_) => {Err(TokenError)}
+            LiteralValue::List(_) => {Err(TokenError)}
+            LiteralValue::Map(_) => {Err(TokenError)}
         }
     }
 }
@@ -271,6 +357,11 @@ impl BitAnd for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::False => {
@@ -298,6 +389,11 @@ impl BitAnd for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::StringValue(s) => {
@@ -316,6 +412,11 @@ impl BitAnd for LiteralValue {
             }
             LiteralValue::IdentifierValue(_) => {Err(TokenError)}
             LiteralValue::Function(_) => {Err(TokenError)}
+            LiteralValue::Instance(_) => {Err(TokenError)}
+            LiteralValue::Host(_) => {Err(TokenError)}
+            LiteralValue::Class(_) => {Err(TokenError)}
+            LiteralValue::List(_) => {Err(TokenError)}
+            LiteralValue::Map(_) => {Err(TokenError)}
         }
     }
 }
@@ -347,6 +448,11 @@ impl BitOr for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::False => {
@@ -370,6 +476,11 @@ impl BitOr for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {Err(TokenError)}
                     LiteralValue::Function(_) => {Err(TokenError)}
+                    LiteralValue::Instance(_) => {Err(TokenError)}
+                    LiteralValue::Host(_) => {Err(TokenError)}
+                    LiteralValue::Class(_) => {Err(TokenError)}
+                    LiteralValue::List(_) => {Err(TokenError)}
+                    LiteralValue::Map(_) => {Err(TokenError)}
                 }
             }
             LiteralValue::StringValue(s) => {
@@ -388,6 +499,11 @@ impl BitOr for LiteralValue {
             }
             LiteralValue::IdentifierValue(_) => {Err(TokenError)}
             LiteralValue::Function(_) => {Err(TokenError)}
+            LiteralValue::Instance(_) => {Err(TokenError)}
+            LiteralValue::Host(_) => {Err(TokenError)}
+            LiteralValue::Class(_) => {Err(TokenError)}
+            LiteralValue::List(_) => {Err(TokenError)}
+            LiteralValue::Map(_) => {Err(TokenError)}
         }
     }
 }
@@ -419,10 +535,20 @@ impl PartialOrd for LiteralValue {
                     }
                     LiteralValue::IdentifierValue(_) => {None}
                     LiteralValue::Function(_) => {None}
+                    LiteralValue::Instance(_) => {None}
+                    LiteralValue::Host(_) => {None}
+                    LiteralValue::Class(_) => {None}
+                    LiteralValue::List(_) => {None}
+                    LiteralValue::Map(_) => {None}
                 }
             }
             LiteralValue::IdentifierValue(_) => {None}
             LiteralValue::Function(_) => {None}
+            LiteralValue::Instance(_) => {None}
+            LiteralValue::Host(_) => {None}
+            LiteralValue::Class(_) => {None}
+            LiteralValue::List(_) => {None}
+            LiteralValue::Map(_) => {None}
         }
     }
 }
@@ -434,6 +560,9 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -446,6 +575,7 @@ pub enum TokenType {
     BangEqual,
     Equal,
     EqualEqual,
+    Arrow,
     Greater,
     GreaterEqual,
     Less,
@@ -456,19 +586,27 @@ pub enum TokenType {
     Number,
     // Keywords
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
     Else,
+    Export,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
     Eof,
@@ -481,6 +619,9 @@ impl fmt::Display for TokenType {
             TokenType::RightParen => write!(f, ")"),
             TokenType::LeftBrace => write!(f, "{{"),
             TokenType::RightBrace => write!(f, "}}"),
+            TokenType::LeftBracket => write!(f, "["),
+            TokenType::RightBracket => write!(f, "]"),
+            TokenType::Colon => write!(f, ":"),
             TokenType::Comma => write!(f, ","),
             TokenType::Dot => write!(f, "."),
             TokenType::Minus => write!(f, "-"),
@@ -493,6 +634,7 @@ impl fmt::Display for TokenType {
             TokenType::BangEqual => write!(f, "!="),
             TokenType::Equal => write!(f, "="),
             TokenType::EqualEqual => write!(f, "=="),
+            TokenType::Arrow => write!(f, "=>"),
             TokenType::Greater => write!(f, ">"),
             TokenType::GreaterEqual => write!(f, ">="),
             TokenType::Less => write!(f, "<"),
@@ -503,19 +645,27 @@ impl fmt::Display for TokenType {
             TokenType::Number => write!(f, "Number"),
             // Keywords
             TokenType::And => write!(f, "&"),
+            TokenType::Break => write!(f, "BREAK"),
+            TokenType::Catch => write!(f, "CATCH"),
             TokenType::Class => write!(f, "CLASS"),
+            TokenType::Continue => write!(f, "CONTINUE"),
             TokenType::Else => write!(f, "ELSE"),
+            TokenType::Export => write!(f, "EXPORT"),
             TokenType::False => write!(f, "FALSE"),
+            TokenType::Finally => write!(f, "FINALLY"),
             TokenType::Fun => write!(f, "FUN"),
             TokenType::For => write!(f, "FOR"),
             TokenType::If => write!(f, "IF"),
+            TokenType::Import => write!(f, "IMPORT"),
             TokenType::Nil => write!(f, "NIL"),
             TokenType::Or => write!(f, "OR"),
             TokenType::Print => write!(f, "PRINT"),
             TokenType::Return => write!(f, "RETURN"),
             TokenType::Super => write!(f, "SUPER"),
             TokenType::This => write!(f, "THIS"),
+            TokenType::Throw => write!(f, "THROW"),
             TokenType::True => write!(f, "TRUE"),
+            TokenType::Try => write!(f, "TRY"),
             TokenType::Var => write!(f, "VAR"),
             TokenType::While => write!(f, "WHILE"),
             TokenType::Eof => write!(f, "EOF")
@@ -523,12 +673,55 @@ impl fmt::Display for TokenType {
     }
 }
 
+/// Region of the source text, offset and length are in bytes while line and
+/// column (counted in characters) are 1-based. A column of 0 marks a span
+/// with no known position, such as one made up by the interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+    pub line: i32,
+    pub column: usize,
+    // Which file the span is in, 0 for the main program and the id of the
+    // loaded source for imported modules
+    pub source: u32,
+}
+
+impl Span {
+    pub fn at_line(line: i32) -> Span {
+        Span {
+            offset: 0,
+            length: 0,
+            line,
+            column: 0,
+            source: 0,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.column > 0
+    }
+
+    /// Smallest span covering both this span and `other`
+    pub fn to(self, other: Span) -> Span {
+        if !other.is_known() { return self; }
+        if !self.is_known() { return other; }
+        let (first, last) = if self.offset <= other.offset { (self, other) } else { (other, self) };
+        let end = (first.offset + first.length).max(last.offset + last.length);
+        Span {
+            length: end - first.offset,
+            ..first
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub(crate) token_type: TokenType,
     pub(crate) lexeme: String,
     pub(crate) literal: LiteralValue, // Object in jlox
     pub(crate) line: i32,
+    pub(crate) span: Span,
 }
 
 impl Token {
@@ -538,6 +731,17 @@ impl Token {
             lexeme,
             literal,
             line,
+            span: Span::at_line(line),
+        }
+    }
+
+    pub fn spanning(token_type: TokenType, lexeme: String, literal: LiteralValue, span: Span) -> Token {
+        Token {
+            token_type,
+            lexeme,
+            literal,
+            line: span.line,
+            span,
         }
     }
 
@@ -0,0 +1,746 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::chunk::{Chunk, OpCode};
+use crate::engine;
+use crate::interpreter::{Interpreter, StackFrame};
+use crate::lox_budget::{Budget, InterruptHandle, Limits};
+use crate::lox_math;
+use crate::lox_native::{Arity, NativeRegistry};
+use crate::token::{LiteralValue, Span, TokenError};
+
+const FRAMES_MAX: usize = 1024;
+
+/// Runtime values of the bytecode virtual machine
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(Rc<String>),
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    /// A native in the VM's `NativeRegistry`, by its id there
+    Native(usize),
+    Class(Rc<RefCell<Class>>),
+    Instance(Rc<RefCell<Instance>>),
+    BoundMethod(Rc<BoundMethod>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A captured variable, open while it still lives in a stack slot and closed
+/// once that slot is popped
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<RefCell<Class>>>,
+    pub methods: HashMap<String, Rc<Closure>>,
+}
+
+pub struct Instance {
+    pub class: Rc<RefCell<Class>>,
+    pub fields: HashMap<String, Value>,
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    slot_base: usize,
+}
+
+impl CallFrame {
+    /// Source of the instruction being executed
+    fn span(&self) -> Span {
+        self.closure.function.chunk.span_at(self.ip.saturating_sub(1))
+    }
+}
+
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<String, Value>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    last_result: Value,
+    natives: NativeRegistry,
+    budget: Budget,
+}
+
+pub struct VmError {
+    pub msg: String,
+    pub span: Span,
+    pub stack: Vec<StackFrame>,
+    /// Set when the script called `exit()`, which isn't reported as an error
+    pub exit_code: Option<u8>,
+}
+
+impl Value {
+    /// Convert plain data values into the tree-walker representation, so
+    /// both backends share the same operator semantics
+    pub fn to_literal(&self) -> Option<LiteralValue> {
+        match self {
+            Value::Nil => { Some(LiteralValue::None) }
+            Value::Bool(true) => { Some(LiteralValue::True) }
+            Value::Bool(false) => { Some(LiteralValue::False) }
+            Value::Number(num) => { Some(LiteralValue::NumValue(*num)) }
+            Value::Str(s) => { Some(LiteralValue::StringValue(s.as_ref().clone())) }
+            _ => { None }
+        }
+    }
+
+    /// Objects are equal only to themselves, matching the tree-walker. None if
+    /// neither side is an object.
+    fn identity_equality(lhs: &Value, rhs: &Value) -> Option<bool> {
+        match (lhs, rhs) {
+            (Value::Function(lhs), Value::Function(rhs)) => { Some(Rc::ptr_eq(lhs, rhs)) }
+            (Value::Closure(lhs), Value::Closure(rhs)) => { Some(Rc::ptr_eq(lhs, rhs)) }
+            (Value::Native(lhs), Value::Native(rhs)) => { Some(lhs == rhs) }
+            (Value::Class(lhs), Value::Class(rhs)) => { Some(Rc::ptr_eq(lhs, rhs)) }
+            (Value::Instance(lhs), Value::Instance(rhs)) => { Some(Rc::ptr_eq(lhs, rhs)) }
+            (Value::BoundMethod(lhs), Value::BoundMethod(rhs)) => { Some(Rc::ptr_eq(lhs, rhs)) }
+            (Value::Nil | Value::Bool(_) | Value::Number(_) | Value::Str(_), Value::Nil | Value::Bool(_) | Value::Number(_) | Value::Str(_)) => {
+                None
+            }
+            _ => { Some(false) }
+        }
+    }
+
+    pub fn from_literal(value: LiteralValue) -> Option<Value> {
+        match value {
+            LiteralValue::None => { Some(Value::Nil) }
+            LiteralValue::True => { Some(Value::Bool(true)) }
+            LiteralValue::False => { Some(Value::Bool(false)) }
+            LiteralValue::NumValue(num) => { Some(Value::Number(num)) }
+            LiteralValue::StringValue(s) => { Some(Value::Str(Rc::new(s))) }
+            _ => { None }
+        }
+    }
+}
+
+impl Class {
+    fn find_method(&self, name: &str) -> Option<Rc<Closure>> {
+        match self.methods.get(name) {
+            Some(method) => { Some(method.clone()) }
+            None => {
+                match &self.superclass {
+                    None => { None }
+                    Some(superclass) => { superclass.borrow().find_method(name) }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Function(function) => write!(f, "<fn {}>", function.name),
+            Value::Closure(closure) => write!(f, "<fn {}>", closure.function.name),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(class) => write!(f, "{}", class.borrow().name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.borrow().name),
+            Value::BoundMethod(bound) => write!(f, "<fn {}>", bound.method.function.name),
+            _ => {
+                match self.to_literal() {
+                    Some(literal) => write!(f, "{}", literal),
+                    None => write!(f, "nil"),
+                }
+            }
+        }
+    }
+}
+
+// Fields can refer back to the instance, so only the class name is printed
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Instance {{ class: {} }}", self.class.borrow().name)
+    }
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let mut globals = HashMap::new();
+        let mut registry = NativeRegistry::new();
+        // Without lists or maps only strings have a length here
+        registry.register("len", Arity::Fixed(1), |args| {
+            Ok(engine::Value::Number(args.get::<String>(0)?.chars().count() as f64))
+        });
+        for (id, name) in registry.entries() {
+            globals.insert(name.to_string(), Value::Native(id));
+        }
+        for (name, value) in lox_math::CONSTANTS {
+            globals.insert(name.to_string(), Value::Number(value));
+        }
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals,
+            open_upvalues: Vec::new(),
+            last_result: Value::Nil,
+            natives: registry,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Names of the globals defined so far, natives included
+    pub fn global_names(&self) -> impl Iterator<Item = &str> {
+        self.globals.keys().map(String::as_str)
+    }
+
+    /// Limit the steps, call depth and time each run may use, see `Limits`
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget.set_limits(limits);
+    }
+
+    /// How big the stack of the thread running the VM is, see `Budget::set_stack_size`
+    pub fn set_stack_size(&mut self, bytes: usize) {
+        self.budget.set_stack_size(bytes);
+    }
+
+    /// Stop runs when `handle` is interrupted
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.budget.set_interrupt_handle(handle);
+    }
+
+    /// Run a compiled script, returning the value of its last top level statement
+    pub fn interpret(&mut self, function: Function) -> Result<Value, VmError> {
+        self.budget.start();
+        let closure = Rc::new(Closure {
+            function: Rc::new(function),
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame { closure, ip: 0, slot_base: 0 });
+        self.last_result = Value::Nil;
+
+        match self.run() {
+            Ok(()) => { Ok(std::mem::replace(&mut self.last_result, Value::Nil)) }
+            Err(err) => {
+                self.stack.clear();
+                self.frames.clear();
+                self.open_upvalues.clear();
+                Err(err)
+            }
+        }
+    }
+
+    fn run(&mut self) -> Result<(), VmError> {
+        loop {
+            let byte = self.read_byte();
+            if let Err(err) = self.budget.step() {
+                return Err(self.error(&err.msg));
+            }
+            let op = match OpCode::from_byte(byte) {
+                Some(op) => { op }
+                None => { return Err(self.error(&format!("Unknown opcode {byte}"))); }
+            };
+            match op {
+                OpCode::Constant => {
+                    let value = self.read_constant();
+                    self.stack.push(value);
+                }
+                OpCode::Nil => { self.stack.push(Value::Nil); }
+                OpCode::True => { self.stack.push(Value::Bool(true)); }
+                OpCode::False => { self.stack.push(Value::Bool(false)); }
+                OpCode::Pop => { self.pop(); }
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let value = self.stack[self.frame().slot_base + slot].clone();
+                    self.stack.push(value);
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let index = self.frame().slot_base + slot;
+                    self.stack[index] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(name.as_str()) {
+                        Some(value) => {
+                            let value = value.clone();
+                            self.stack.push(value);
+                        }
+                        None => { return Err(self.error(&format!("{} not defined", name))); }
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name.as_ref().clone(), value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    if !self.globals.contains_key(name.as_str()) {
+                        return Err(self.error(&format!("Variable {} not yet declared", name)));
+                    }
+                    let value = self.peek(0).clone();
+                    self.globals.insert(name.as_ref().clone(), value);
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => { self.stack[*slot].clone() }
+                        Upvalue::Closed(value) => { value.clone() }
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => { self.stack[*slot] = value; }
+                        Upvalue::Closed(closed) => { *closed = value; }
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let instance = match self.peek(0) {
+                        Value::Instance(instance) => { instance.clone() }
+                        _ => { return Err(self.error("Only instances have properties.")); }
+                    };
+                    let field = instance.borrow().fields.get(name.as_str()).cloned();
+                    let value = match field {
+                        Some(value) => { value }
+                        None => {
+                            let class = instance.borrow().class.clone();
+                            let method = class.borrow().find_method(&name);
+                            match method {
+                                Some(method) => {
+                                    Value::BoundMethod(Rc::new(BoundMethod {
+                                        receiver: Value::Instance(instance.clone()),
+                                        method,
+                                    }))
+                                }
+                                None => { return Err(self.error(&format!("Undefined property '{}'.", name))); }
+                            }
+                        }
+                    };
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    let instance = match self.pop() {
+                        Value::Instance(instance) => { instance }
+                        _ => { return Err(self.error("Only instances have fields.")); }
+                    };
+                    instance.borrow_mut().fields.insert(name.as_ref().clone(), value.clone());
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = match self.pop() {
+                        Value::Class(class) => { class }
+                        _ => { return Err(self.error("Can't use 'super' in a class with no superclass.")); }
+                    };
+                    let receiver = self.pop();
+                    let method = superclass.borrow().find_method(&name);
+                    match method {
+                        Some(method) => {
+                            self.stack.push(Value::BoundMethod(Rc::new(BoundMethod { receiver, method })));
+                        }
+                        None => { return Err(self.error(&format!("Undefined property '{}'.", name))); }
+                    }
+                }
+                OpCode::Equal | OpCode::NotEqual | OpCode::Greater | OpCode::GreaterEqual
+                | OpCode::Less | OpCode::LessEqual => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let result = self.compare(op, &lhs, &rhs)?;
+                    self.stack.push(Value::Bool(result));
+                }
+                OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let result = self.arithmetic(op, lhs, rhs)?;
+                    self.stack.push(result);
+                }
+                OpCode::Not | OpCode::Negate => {
+                    let operand = self.pop();
+                    let result = match operand.to_literal() {
+                        Some(literal) => {
+                            if op == OpCode::Not { !literal } else { -literal }
+                        }
+                        None => { Err(TokenError) }
+                    };
+                    let value = result.ok().and_then(Value::from_literal);
+                    match value {
+                        Some(value) => { self.stack.push(value); }
+                        None => {
+                            let msg = if op == OpCode::Not { "Invalid Not Operation" } else { "Invalid Negative Operation" };
+                            return Err(self.error(msg));
+                        }
+                    }
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    println!("{}", value);
+                    if io::stdout().flush().is_err() {
+                        return Err(self.error("Error Flushing StdOut in Print Statement"));
+                    }
+                }
+                OpCode::SetResult => {
+                    self.last_result = self.pop();
+                }
+                OpCode::Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if !self.is_truthy(self.peek(0))? {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    let arg_count = self.read_byte() as usize;
+                    let callee = self.peek(arg_count).clone();
+                    self.call_value(callee, arg_count)?;
+                }
+                OpCode::Closure => {
+                    let function = match self.read_constant() {
+                        Value::Function(function) => { function }
+                        _ => { return Err(self.error("Closure operand is not a function")); }
+                    };
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        if is_local {
+                            let slot = self.frame().slot_base + index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        } else {
+                            upvalues.push(self.frame().closure.upvalues[index].clone());
+                        }
+                    }
+                    self.stack.push(Value::Closure(Rc::new(Closure { function, upvalues })));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("Return with no call frame");
+                    self.close_upvalues(frame.slot_base);
+                    self.stack.truncate(frame.slot_base);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.stack.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    self.stack.push(Value::Class(Rc::new(RefCell::new(Class {
+                        name: name.as_ref().clone(),
+                        superclass: None,
+                        methods: HashMap::new(),
+                    }))));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1) {
+                        Value::Class(class) => { class.clone() }
+                        _ => { return Err(self.error("Superclass must be a class.")); }
+                    };
+                    if let Value::Class(subclass) = self.peek(0) {
+                        subclass.borrow_mut().superclass = Some(superclass);
+                    }
+                    self.pop();
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    let method = match self.pop() {
+                        Value::Closure(closure) => { closure }
+                        _ => { return Err(self.error("Method is not a function")); }
+                    };
+                    if let Value::Class(class) = self.peek(0) {
+                        class.borrow_mut().methods.insert(name.as_ref().clone(), method);
+                    }
+                }
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<(), VmError> {
+        match callee {
+            Value::Closure(closure) => { self.call(closure, arg_count) }
+            Value::BoundMethod(bound) => {
+                let receiver_slot = self.stack.len() - arg_count - 1;
+                self.stack[receiver_slot] = bound.receiver.clone();
+                self.call(bound.method.clone(), arg_count)
+            }
+            Value::Class(class) => {
+                let receiver_slot = self.stack.len() - arg_count - 1;
+                let instance = Value::Instance(Rc::new(RefCell::new(Instance {
+                    class: class.clone(),
+                    fields: HashMap::new(),
+                })));
+                self.stack[receiver_slot] = instance;
+                let initializer = class.borrow().find_method("init");
+                match initializer {
+                    Some(initializer) => { self.call(initializer, arg_count) }
+                    None => {
+                        if arg_count != 0 {
+                            return Err(self.error("Incorrect number of arguments"));
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            Value::Native(id) => {
+                if !self.natives.arity(id).accepts(arg_count) {
+                    return Err(self.error("Incorrect number of arguments"));
+                }
+                // Natives count towards the depth like they do in the tree-walker
+                if let Err(err) = self.budget.enter_call(self.frames.len() - 1) {
+                    return Err(self.error(&err.msg));
+                }
+                let first_arg = self.stack.len() - arg_count;
+                let mut arguments = Vec::with_capacity(arg_count);
+                for value in &self.stack[first_arg..] {
+                    match value.to_literal() {
+                        Some(literal) => { arguments.push(literal); }
+                        None => { return Err(self.error("Invalid argument to native function")); }
+                    }
+                }
+                let result = match self.natives.call_scalars(id, arguments) {
+                    Ok(result) => { result }
+                    Err(err) => {
+                        let mut vm_err = self.error(&err.msg);
+                        vm_err.exit_code = err.exit_code();
+                        return Err(vm_err);
+                    }
+                };
+                let value = match Value::from_literal(result) {
+                    Some(value) => { value }
+                    None => { return Err(self.error("Invalid native function result")); }
+                };
+                self.stack.truncate(first_arg - 1);
+                self.stack.push(value);
+                Ok(())
+            }
+            _ => { Err(self.error("Tried to call non-callable")) }
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, arg_count: usize) -> Result<(), VmError> {
+        if arg_count != closure.function.arity {
+            return Err(self.error("Incorrect number of arguments"));
+        }
+        // The script's own frame isn't a call
+        if let Err(err) = self.budget.enter_call(self.frames.len() - 1) {
+            return Err(self.error(&err.msg));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error("Stack overflow."));
+        }
+        let slot_base = self.stack.len() - arg_count - 1;
+        self.frames.push(CallFrame { closure, ip: 0, slot_base });
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        for upvalue in &self.open_upvalues {
+            if let Upvalue::Open(open_slot) = &*upvalue.borrow() {
+                if *open_slot == slot {
+                    return upvalue.clone();
+                }
+            }
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Move every captured variable living at or above `last_slot` off the stack
+    fn close_upvalues(&mut self, last_slot: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let slot = match &*upvalue.borrow() {
+                Upvalue::Open(slot) => { *slot }
+                Upvalue::Closed(_) => { return false; }
+            };
+            if slot >= last_slot {
+                *upvalue.borrow_mut() = Upvalue::Closed(stack[slot].clone());
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    fn compare(&self, op: OpCode, lhs: &Value, rhs: &Value) -> Result<bool, VmError> {
+        if op == OpCode::Equal || op == OpCode::NotEqual {
+            if let Some(equal) = Value::identity_equality(lhs, rhs) {
+                return Ok(equal == (op == OpCode::Equal));
+            }
+        }
+        let ordering = match (lhs.to_literal(), rhs.to_literal()) {
+            (Some(lhs), Some(rhs)) => { lhs.partial_cmp(&rhs) }
+            _ => { None }
+        };
+        // NaN is unordered, so comparing with it is false, apart from !=
+        let unordered = [lhs, rhs].iter().any(|value| matches!(value, Value::Number(num) if num.is_nan()));
+        let ordering = match ordering {
+            Some(ordering) => { ordering }
+            None if unordered => { return Ok(op == OpCode::NotEqual); }
+            None => {
+                let msg = match op {
+                    OpCode::Equal => { "Invalid Equality Comparison" }
+                    OpCode::NotEqual => { "Invalid Inequality Comparison" }
+                    OpCode::Greater => { "Invalid Greater Comparison" }
+                    OpCode::GreaterEqual => { "Invalid Greater/Equal Comparison" }
+                    OpCode::Less => { "Invalid Less Comparison" }
+                    _ => { "Invalid Less/Equal Comparison" }
+                };
+                return Err(self.error(msg));
+            }
+        };
+        Ok(match op {
+            OpCode::Equal => { ordering.is_eq() }
+            OpCode::NotEqual => { ordering.is_ne() }
+            OpCode::Greater => { ordering.is_gt() }
+            OpCode::GreaterEqual => { ordering.is_ge() }
+            OpCode::Less => { ordering.is_lt() }
+            _ => { ordering.is_le() }
+        })
+    }
+
+    fn arithmetic(&self, op: OpCode, lhs: Value, rhs: Value) -> Result<Value, VmError> {
+        let result = match (lhs.to_literal(), rhs.to_literal()) {
+            (Some(lhs), Some(rhs)) => {
+                match op {
+                    OpCode::Add => { lhs + rhs }
+                    OpCode::Subtract => { lhs - rhs }
+                    OpCode::Multiply => { lhs * rhs }
+                    _ => { lhs / rhs }
+                }
+            }
+            _ => { Err(TokenError) }
+        };
+        match result.ok().and_then(Value::from_literal) {
+            Some(value) => { Ok(value) }
+            None => {
+                let msg = match op {
+                    OpCode::Add => { "Invalid Addition" }
+                    OpCode::Subtract => { "Invalid Subtraction" }
+                    OpCode::Multiply => { "Invalid Multiplication" }
+                    _ => { "Invalid Division" }
+                };
+                Err(self.error(msg))
+            }
+        }
+    }
+
+    fn is_truthy(&self, value: &Value) -> Result<bool, VmError> {
+        match value {
+            Value::Instance(_) => { Ok(true) }
+            Value::Class(_) => { Err(self.error("Tried to evaluate truthiness of class")) }
+            Value::Function(_) | Value::Closure(_) | Value::Native(_) | Value::BoundMethod(_) => {
+                Err(self.error("Tried to evaluate truthiness of callable"))
+            }
+            _ => {
+                let literal = value.to_literal().unwrap_or(LiteralValue::None);
+                Interpreter::is_truthy(&literal).map_err(|err| self.error(&err.msg))
+            }
+        }
+    }
+
+    fn error(&self, msg: &str) -> VmError {
+        let span = match self.frames.last() {
+            None => { Span::default() }
+            Some(frame) => { frame.span() }
+        };
+        // Every frame but the top level script was called from the frame below it
+        let stack = self.frames.windows(2).rev()
+            .map(|pair| StackFrame {
+                function: pair[1].closure.function.name.clone(),
+                line: pair[0].span().line,
+            })
+            .collect();
+        VmError {
+            msg: msg.to_string(),
+            span,
+            stack,
+            exit_code: None,
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("No active call frame")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("No active call frame")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let value = frame.closure.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_u16() as usize;
+        self.frame().closure.function.chunk.constants[index].clone()
+    }
+
+    fn read_string(&mut self) -> Rc<String> {
+        match self.read_constant() {
+            Value::Str(s) => { s }
+            _ => { Rc::new(String::new()) }
+        }
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("Stack underflow")
+    }
+}
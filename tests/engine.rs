@@ -0,0 +1,285 @@
+//! The embedding API, driven the way a Rust host would use it
+
+use std::thread;
+use std::time::Duration;
+
+use loxrs::{Args, Arity, Engine, Error, HostObject, HostRef, LimitExceeded, Limits, Value};
+
+fn heap_objects(engine: &mut Engine) -> f64 {
+    match engine.eval("heap_objects();") {
+        Ok(Value::Number(count)) => { count }
+        other => { panic!("heap_objects() gave {other:?}") }
+    }
+}
+
+#[test]
+fn eval_returns_the_last_statement() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.eval("1 + 2;").unwrap(), Value::Number(3.0));
+    assert_eq!(engine.eval("\"a\" + \"b\";").unwrap(), Value::String("ab".to_string()));
+    assert_eq!(engine.eval("var x = 1;").unwrap(), Value::Nil);
+}
+
+#[test]
+fn eval_copies_lists_and_maps_out() {
+    let mut engine = Engine::new();
+    let list = engine.eval("[1, \"two\", [true]];").unwrap();
+    let expected = Value::List(vec![
+        Value::Number(1.0),
+        Value::String("two".to_string()),
+        Value::List(vec![Value::Bool(true)]),
+    ]);
+    assert_eq!(list, expected);
+    let map = engine.eval("var m = {\"a\": nil}; m;").unwrap();
+    assert_eq!(map, Value::Map(vec![(Value::String("a".to_string()), Value::Nil)]));
+}
+
+#[test]
+fn globals_persist_between_evals() {
+    let mut engine = Engine::new();
+    engine.eval("var count = 1;").unwrap();
+    engine.eval("count = count + 1;").unwrap();
+    assert_eq!(engine.get_global("count").unwrap(), Value::Number(2.0));
+
+    engine.set_global("greeting", Value::String("hi".to_string())).unwrap();
+    assert_eq!(engine.eval("greeting + \"!\";").unwrap(), Value::String("hi!".to_string()));
+
+    engine.set_global("items", Value::List(vec![Value::Number(1.0)])).unwrap();
+    assert_eq!(engine.eval("push(items, 2); len(items);").unwrap(), Value::Number(2.0));
+
+    assert!(matches!(engine.get_global("missing"), Err(Error::Runtime { .. })));
+}
+
+#[test]
+fn call_runs_functions_and_classes() {
+    let mut engine = Engine::new();
+    engine.eval("fun add(a, b) { return a + b; } class Point { init(x) { this.x = x; } }").unwrap();
+    assert_eq!(engine.call("add", &[Value::Number(2.0), Value::Number(3.0)]).unwrap(), Value::Number(5.0));
+
+    let point = engine.call("Point", &[Value::Number(4.0)]).unwrap();
+    assert!(matches!(point, Value::Instance(_)));
+    engine.set_global("p", point).unwrap();
+    assert_eq!(engine.eval("p.x;").unwrap(), Value::Number(4.0));
+
+    let add = engine.get_global("add").unwrap();
+    assert!(matches!(add, Value::Function(_)));
+    assert_eq!(engine.call_value(&add, &[Value::Number(1.0), Value::Number(1.0)]).unwrap(), Value::Number(2.0));
+    assert!(matches!(engine.call_value(&Value::Number(1.0), &[]), Err(Error::Runtime { .. })));
+}
+
+#[test]
+fn compile_errors_list_every_problem() {
+    let mut engine = Engine::new();
+    match engine.eval("var = 1;\nprint;") {
+        Err(Error::Compile(diagnostics)) => {
+            assert_eq!(diagnostics.len(), 2);
+            assert_eq!(diagnostics[0].line, 1);
+            assert_eq!(diagnostics[1].line, 2);
+        }
+        other => { panic!("expected a compile error, got {other:?}") }
+    }
+}
+
+#[test]
+fn runtime_errors_carry_the_line_and_stack() {
+    let mut engine = Engine::new();
+    engine.eval("fun fail() {\n  return 1 + nil;\n}").unwrap();
+    match engine.eval("fail();") {
+        Err(Error::Runtime { line, stack, .. }) => {
+            assert_eq!(line, Some(2));
+            assert_eq!(stack.len(), 1);
+            assert_eq!(stack[0].function, "fail");
+        }
+        other => { panic!("expected a runtime error, got {other:?}") }
+    }
+    match engine.eval("throw \"oops\";") {
+        Err(Error::Runtime { message, .. }) => { assert!(message.contains("oops"), "{message}"); }
+        other => { panic!("expected a runtime error, got {other:?}") }
+    }
+}
+
+#[test]
+fn exit_and_limits_have_their_own_errors() {
+    let mut engine = Engine::new();
+    assert!(matches!(engine.eval("exit(4);"), Err(Error::Exit(4))));
+
+    engine.set_limits(Limits { max_steps: Some(100), ..Limits::default() });
+    match engine.eval("while (true) {}") {
+        Err(Error::Limit { kind, .. }) => { assert_eq!(kind, LimitExceeded::Steps); }
+        other => { panic!("expected the step limit, got {other:?}") }
+    }
+    // Each run gets a fresh budget
+    assert_eq!(engine.eval("1;").unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn handles_keep_objects_alive_until_dropped() {
+    let mut engine = Engine::new();
+    engine.eval("class Box { init(n) { this.n = n; } }").unwrap();
+    engine.eval("gc();").unwrap();
+    let before = heap_objects(&mut engine);
+
+    let boxed = engine.call("Box", &[Value::Number(7.0)]).unwrap();
+    let copy = boxed.clone();
+    engine.eval("gc();").unwrap();
+    assert!(heap_objects(&mut engine) > before);
+
+    // A clone shares the root of the handle it came from
+    drop(boxed);
+    engine.eval("gc();").unwrap();
+    engine.set_global("b", copy).unwrap();
+    assert_eq!(engine.eval("b.n;").unwrap(), Value::Number(7.0));
+
+    engine.eval("b = nil; gc();").unwrap();
+    assert_eq!(heap_objects(&mut engine), before);
+}
+
+#[test]
+fn dropped_handles_are_not_leaked() {
+    let mut engine = Engine::new();
+    engine.eval("class Thing {} fun make() { return Thing(); }").unwrap();
+    engine.eval("gc();").unwrap();
+    let before = heap_objects(&mut engine);
+    for _ in 0..1000 {
+        let thing = engine.call("make", &[]).unwrap();
+        assert!(matches!(thing, Value::Instance(_)));
+    }
+    engine.eval("gc();").unwrap();
+    assert_eq!(heap_objects(&mut engine), before);
+}
+
+/// Remembers the last value it was given, through a method or a property
+struct Holder {
+    held: Value,
+}
+
+impl HostObject for Holder {
+    fn type_name(&self) -> &str {
+        "Holder"
+    }
+
+    fn get(&self, name: &str) -> Option<Value> {
+        (name == "held").then(|| self.held.clone())
+    }
+
+    fn set(&mut self, name: &str, value: Value) -> Result<(), Error> {
+        match name {
+            "held" => {
+                self.held = value;
+                Ok(())
+            }
+            _ => { Err(Error::runtime(format!("Can't set '{name}' on Holder."))) }
+        }
+    }
+
+    fn method(&self, name: &str) -> Option<Arity> {
+        (name == "kind").then_some(Arity::Fixed(1))
+    }
+
+    fn call_method(&mut self, _name: &str, args: &Args) -> Result<Value, Error> {
+        let kind = match args.get::<Value>(0)? {
+            Value::Instance(_) => { "instance" }
+            _ => { "other" }
+        };
+        Ok(Value::from(kind))
+    }
+}
+
+#[test]
+fn arguments_are_only_rooted_during_the_call() {
+    let mut engine = Engine::new();
+    engine.register_native("kind", Arity::Fixed(1), |args| {
+        Ok(Value::Bool(matches!(args.get::<Value>(0)?, Value::Instance(_))))
+    });
+    engine.set_global("holder", Value::Host(HostRef::new(Holder { held: Value::Nil }))).unwrap();
+    engine.eval("class Thing {}").unwrap();
+    engine.eval("gc();").unwrap();
+    let before = heap_objects(&mut engine);
+
+    engine.eval("for (var i = 0; i < 1000; i = i + 1) { kind(Thing()); holder.kind(Thing()); }").unwrap();
+    engine.eval("gc();").unwrap();
+    assert_eq!(heap_objects(&mut engine), before);
+}
+
+#[test]
+fn host_objects_keep_what_they_hold_alive() {
+    let mut engine = Engine::new();
+    engine.set_global("holder", Value::Host(HostRef::new(Holder { held: Value::Nil }))).unwrap();
+    engine.eval("class Thing { init(n) { this.n = n; } }").unwrap();
+    engine.eval("gc();").unwrap();
+    let before = heap_objects(&mut engine);
+
+    engine.eval("for (var i = 0; i < 100; i = i + 1) { holder.held = Thing(i); }").unwrap();
+    engine.eval("gc();").unwrap();
+    // Only the instance held last is still rooted
+    assert_eq!(engine.eval("holder.held.n;").unwrap(), Value::Number(99.0));
+    assert_eq!(heap_objects(&mut engine), before + 1.0);
+
+    engine.eval("holder.held = nil; gc();").unwrap();
+    assert_eq!(heap_objects(&mut engine), before);
+}
+
+#[test]
+fn an_interrupt_sent_between_runs_stops_the_next_one() {
+    let mut engine = Engine::new();
+    let handle = engine.interrupt_handle();
+    handle.interrupt();
+    match engine.eval("print \"running\";") {
+        Err(Error::Limit { kind, .. }) => { assert_eq!(kind, LimitExceeded::Interrupted); }
+        other => { panic!("expected an interrupt, got {other:?}") }
+    }
+    // The interrupt was used up by the run it stopped
+    assert_eq!(engine.eval("1;").unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn interrupts_stop_a_running_script_from_another_thread() {
+    let mut engine = Engine::new();
+    let handle = engine.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+    match engine.eval("while (true) {}") {
+        Err(Error::Limit { kind, .. }) => { assert_eq!(kind, LimitExceeded::Interrupted); }
+        other => { panic!("expected an interrupt, got {other:?}") }
+    }
+    interrupter.join().unwrap();
+}
+
+#[test]
+fn runaway_recursion_fails_instead_of_overflowing_the_stack() {
+    let mut engine = Engine::new();
+    engine.eval("fun forever(n) { return forever(n + 1) + 1; }").unwrap();
+    match engine.eval("forever(0);") {
+        Err(Error::Limit { kind, .. }) => { assert_eq!(kind, LimitExceeded::CallDepth); }
+        other => { panic!("expected the call depth limit, got {other:?}") }
+    }
+    // The engine is still usable afterwards
+    assert_eq!(engine.eval("1 + 1;").unwrap(), Value::Number(2.0));
+}
+
+#[test]
+fn handles_only_work_in_the_engine_they_came_from() {
+    let mut first = Engine::new();
+    let mut second = Engine::new();
+    first.eval("class Point { init(x) { this.x = x; } } fun id(v) { return v; }").unwrap();
+    second.eval("class Other {} var filler = Other(); fun id(v) { return v; }").unwrap();
+    let point = first.call("Point", &[Value::Number(1.0)]).unwrap();
+    let id = first.get_global("id").unwrap();
+
+    for result in [
+        second.set_global("p", point.clone()),
+        second.call("id", &[point.clone()]).map(|_| ()),
+        second.call_value(&id, &[]).map(|_| ()),
+        second.set_global("nested", Value::List(vec![point.clone()])),
+    ] {
+        match result {
+            Err(Error::Runtime { message, .. }) => { assert_eq!(message, "Handle belongs to a different engine."); }
+            other => { panic!("expected the handle to be rejected, got {other:?}") }
+        }
+    }
+    // It still works where it came from
+    first.set_global("p", point).unwrap();
+    assert_eq!(first.eval("p.x;").unwrap(), Value::Number(1.0));
+}
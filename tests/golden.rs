@@ -0,0 +1,162 @@
+//! Runs the programs in tests/programs through the loxrs binary and compares
+//! what they print and their exit status with the `.out` file next to each.
+
+use std::{env, fs};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+struct Run {
+    stdout: String,
+    status: i32,
+}
+
+fn programs_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("programs")
+}
+
+/// What a program is run with besides its own source
+#[derive(Default)]
+struct Input<'a> {
+    options: &'a [&'a str],
+    args: &'a [&'a str],
+    env: &'a [(&'a str, &'a str)],
+    stdin: &'a str,
+}
+
+/// Run a program from inside tests/programs, so it can read the files there
+fn run(name: &str, input: &Input) -> Run {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_loxrs"))
+        .args(input.options)
+        .arg(format!("{name}.lox"))
+        .args(input.args)
+        .envs(input.env.iter().copied())
+        .current_dir(programs_dir())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run loxrs");
+    child.stdin.take().unwrap().write_all(input.stdin.as_bytes()).expect("failed to write stdin");
+    let output = child.wait_with_output().expect("failed to run loxrs");
+    Run {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        status: output.status.code().unwrap_or(-1),
+    }
+}
+
+fn expected(name: &str) -> String {
+    let path = programs_dir().join(format!("{name}.out"));
+    fs::read_to_string(&path).unwrap_or_else(|err| panic!("can't read {}: {err}", path.display()))
+}
+
+fn check(name: &str, label: &str, run: Run, status: i32) {
+    assert_eq!(run.stdout, expected(name), "output of {name}.lox on {label}");
+    assert_eq!(run.status, status, "exit status of {name}.lox on {label}");
+}
+
+/// Both backends must print exactly what the `.out` file holds
+fn check_both(name: &str, status: i32) {
+    check_both_with(name, &Input::default(), status);
+}
+
+fn check_both_with(name: &str, input: &Input, status: i32) {
+    check(name, "the tree-walker", run(name, input), status);
+    let options = [input.options, &["--vm"]].concat();
+    check(name, "the VM", run(name, &Input { options: &options, ..*input }), status);
+}
+
+/// For programs using features only the tree-walker has. They are run a
+/// second time collecting before every statement, which must not change anything.
+fn check_tree_walker(name: &str, status: i32) {
+    check_tree_walker_with(name, &Input::default(), status);
+}
+
+fn check_tree_walker_with(name: &str, input: &Input, status: i32) {
+    check(name, "the tree-walker", run(name, input), status);
+    let mut env = input.env.to_vec();
+    env.push(("LOXRS_GC_THRESHOLD", "1"));
+    let stressed = Input { env: &env, ..*input };
+    check(name, "the tree-walker under GC stress", run(name, &stressed), status);
+}
+
+macro_rules! golden {
+    ($check:ident: $($name:ident => $status:expr),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                $check(stringify!($name), $status);
+            }
+        )*
+    };
+}
+
+golden!(check_both:
+    expressions => 0,
+    scopes => 0,
+    closures => 0,
+    control_flow => 0,
+    classes => 0,
+    equality => 0,
+    math => 0,
+    runtime_error => 70,
+    static_errors => 65,
+    strings => 70,
+    exit => 3,
+    recursion => 0,
+    superclass_error => 70,
+    traceback => 70,
+    args_shadowed => 0,
+);
+
+golden!(check_tree_walker:
+    lists => 70,
+    maps => 70,
+    exceptions => 0,
+    gc => 0,
+    json => 0,
+    import => 0,
+    import_error => 65,
+);
+
+#[test]
+fn file_io() {
+    // Each run gets an empty scratch directory, as the program starts by checking its file isn't there
+    for (label, stressed) in [("the tree-walker", false), ("the tree-walker under GC stress", true)] {
+        let scratch = env::temp_dir().join(format!("loxrs-file-io-{}-{stressed}", process::id()));
+        fs::create_dir_all(&scratch).expect("failed to make a scratch directory");
+        let mut vars = vec![("LOXRS_SCRATCH", scratch.to_str().unwrap())];
+        if stressed {
+            vars.push(("LOXRS_GC_THRESHOLD", "1"));
+        }
+        let input = Input { env: &vars, stdin: "hello\nworld", ..Input::default() };
+        check("file_io", label, run("file_io", &input), 0);
+        fs::remove_dir_all(&scratch).expect("failed to remove the scratch directory");
+    }
+}
+
+#[test]
+fn sandbox() {
+    let input = Input { options: &["--sandbox"], stdin: "typed\n", ..Input::default() };
+    check_tree_walker_with("sandbox", &input, 70);
+    check_tree_walker_with("sandbox_import", &input, 70);
+}
+
+#[test]
+fn args() {
+    let input = Input { args: &["one", "two words"], ..Input::default() };
+    check_tree_walker_with("args", &input, 0);
+    check("args_vm", "the VM", run("args_vm", &Input { options: &["--vm"], ..input }), 65);
+}
+
+#[test]
+fn getenv() {
+    let vars = [("LOXRS_GREETING", "hi")];
+    check_tree_walker_with("getenv", &Input { env: &vars, ..Input::default() }, 0);
+    check("getenv", "the VM", run("getenv", &Input { options: &["--vm"], env: &vars, ..Input::default() }), 0);
+}
+
+#[test]
+fn limits() {
+    check_both_with("step_limit", &Input { options: &["--max-steps", "1000"], ..Input::default() }, 70);
+    check_both_with("call_depth", &Input { options: &["--max-depth", "50"], ..Input::default() }, 70);
+}